@@ -0,0 +1,79 @@
+package com.example.myapplication
+
+import androidx.compose.foundation.Canvas
+import androidx.compose.foundation.layout.BoxWithConstraints
+import androidx.compose.foundation.layout.Column
+import androidx.compose.foundation.layout.fillMaxSize
+import androidx.compose.foundation.layout.offset
+import androidx.compose.foundation.layout.padding
+import androidx.compose.foundation.layout.size
+import androidx.compose.material3.Text
+import androidx.compose.runtime.Composable
+import androidx.compose.ui.Alignment
+import androidx.compose.ui.Modifier
+import androidx.compose.ui.geometry.Offset
+import androidx.compose.ui.geometry.Size
+import androidx.compose.ui.graphics.Color
+import androidx.compose.ui.graphics.drawscope.Stroke
+import androidx.compose.ui.graphics.drawscope.drawIntoCanvas
+import androidx.compose.ui.graphics.nativeCanvas
+import androidx.compose.ui.unit.dp
+
+// Draws the pad zones, mapped monitor, profile and forwarding indicator sent by the bridge.
+@Composable
+fun BridgeOverlay(control: ControlConnection, modifier: Modifier = Modifier) {
+    BoxWithConstraints(modifier = modifier.fillMaxSize()) {
+        Canvas(modifier = Modifier.fillMaxSize()) {
+            for (zone in control.zones) {
+                drawRect(
+                    color = Color.DarkGray,
+                    topLeft = Offset(zone.x * size.width, zone.y * size.height),
+                    size = Size(zone.width * size.width, zone.height * size.height),
+                    style = Stroke(width = 2.dp.toPx()),
+                )
+            }
+        }
+
+        for (zone in control.zones) {
+            Text(
+                text = zone.name,
+                color = Color.Gray,
+                modifier = Modifier
+                    .offset(x = maxWidth * zone.x, y = maxHeight * zone.y)
+                    .padding(8.dp),
+            )
+        }
+
+        Column(
+            modifier = Modifier.align(Alignment.TopEnd).padding(16.dp),
+            horizontalAlignment = Alignment.End,
+        ) {
+            Canvas(modifier = Modifier.size(12.dp)) {
+                drawCircle(color = if (control.forwarding) Color.Green else Color.DarkGray)
+            }
+            control.monitor?.let { Text(text = it, color = Color.Gray) }
+            control.profile?.let { Text(text = it, color = Color.Gray) }
+        }
+    }
+}
+
+@Composable
+fun SidecarView(sidecar: SidecarConnection, modifier: Modifier = Modifier) {
+    Canvas(modifier = modifier.fillMaxSize()) {
+        // Reading the frame counter redraws the canvas whenever new tiles arrive.
+        if (sidecar.frame < 0) {
+            return@Canvas
+        }
+        val bitmap = sidecar.bitmap ?: return@Canvas
+        synchronized(bitmap) {
+            drawIntoCanvas {
+                it.nativeCanvas.drawBitmap(
+                    bitmap,
+                    null,
+                    android.graphics.Rect(0, 0, size.width.toInt(), size.height.toInt()),
+                    null,
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,131 @@
+package com.example.myapplication
+
+import android.os.Build
+import android.util.DisplayMetrics
+import android.view.MotionEvent
+import java.io.BufferedOutputStream
+import java.io.DataOutputStream
+import java.io.IOException
+import java.net.Socket
+import java.util.concurrent.Executors
+import java.util.concurrent.ScheduledFuture
+import java.util.concurrent.TimeUnit
+
+// Streams stylus MotionEvents to android-tablet-bridge through `adb reverse tcp:PORT tcp:PORT`.
+// The wire format is described in src/companion.rs.
+class CompanionConnection(private val metrics: DisplayMetrics, private val port: Int = DEFAULT_PORT) {
+    private val executor = Executors.newSingleThreadScheduledExecutor()
+    private var output: DataOutputStream? = null
+    private var ping: ScheduledFuture<*>? = null
+
+    fun start() {
+        ping = executor.scheduleWithFixedDelay({
+            try {
+                val stream = output ?: connect()
+                stream.writeByte(PACKET_PING)
+                stream.flush()
+            } catch (e: IOException) {
+                close()
+            }
+        }, 0, 1, TimeUnit.SECONDS)
+    }
+
+    fun stop() {
+        ping?.cancel(false)
+        executor.execute { close() }
+    }
+
+    fun send(event: MotionEvent) {
+        val tool = event.getToolType(0)
+        if (tool != MotionEvent.TOOL_TYPE_STYLUS && tool != MotionEvent.TOOL_TYPE_ERASER) {
+            return
+        }
+
+        val action = event.actionMasked
+        val buttons = (if (event.isButtonPressed(MotionEvent.BUTTON_STYLUS_PRIMARY)) 1 else 0) or
+            (if (event.isButtonPressed(MotionEvent.BUTTON_STYLUS_SECONDARY)) 2 else 0)
+        val samples = (0 until event.historySize).map { i ->
+            Sample(
+                event.getHistoricalEventTime(i) * 1000,
+                event.getHistoricalX(0, i),
+                event.getHistoricalY(0, i),
+                event.getHistoricalPressure(0, i),
+                event.getHistoricalAxisValue(MotionEvent.AXIS_TILT, 0, i),
+                event.getHistoricalOrientation(0, i),
+            )
+        } + Sample(
+            event.eventTime * 1000,
+            event.x,
+            event.y,
+            event.pressure,
+            event.getAxisValue(MotionEvent.AXIS_TILT),
+            event.orientation,
+        )
+
+        executor.execute {
+            val stream = output ?: return@execute
+            try {
+                stream.writeByte(PACKET_MOTION)
+                stream.writeByte(action)
+                stream.writeByte(tool)
+                stream.writeByte(buttons)
+                stream.writeShort(samples.size)
+                for (sample in samples) {
+                    stream.writeLong(sample.timeMicros)
+                    stream.writeFloat(sample.x)
+                    stream.writeFloat(sample.y)
+                    stream.writeFloat(sample.pressure)
+                    stream.writeFloat(sample.tilt)
+                    stream.writeFloat(sample.orientation)
+                }
+                stream.flush()
+            } catch (e: IOException) {
+                close()
+            }
+        }
+    }
+
+    private fun connect(): DataOutputStream {
+        val socket = Socket("127.0.0.1", port)
+        socket.tcpNoDelay = true
+
+        val stream = DataOutputStream(BufferedOutputStream(socket.getOutputStream()))
+        val name = Build.MODEL.toByteArray(Charsets.UTF_8)
+        stream.write("ATB".toByteArray(Charsets.US_ASCII))
+        stream.writeByte(VERSION)
+        stream.writeShort(name.size)
+        stream.write(name)
+        stream.writeInt(metrics.widthPixels)
+        stream.writeInt(metrics.heightPixels)
+        stream.writeFloat(metrics.xdpi)
+        stream.writeFloat(metrics.ydpi)
+        stream.flush()
+
+        output = stream
+        return stream
+    }
+
+    private fun close() {
+        try {
+            output?.close()
+        } catch (e: IOException) {
+        }
+        output = null
+    }
+
+    private data class Sample(
+        val timeMicros: Long,
+        val x: Float,
+        val y: Float,
+        val pressure: Float,
+        val tilt: Float,
+        val orientation: Float,
+    )
+
+    companion object {
+        const val DEFAULT_PORT = 27183
+        private const val VERSION = 1
+        private const val PACKET_MOTION = 1
+        private const val PACKET_PING = 2
+    }
+}
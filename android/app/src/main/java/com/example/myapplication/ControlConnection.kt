@@ -0,0 +1,94 @@
+package com.example.myapplication
+
+import androidx.compose.runtime.getValue
+import androidx.compose.runtime.mutableStateOf
+import androidx.compose.runtime.setValue
+import java.io.BufferedInputStream
+import java.io.DataInputStream
+import java.io.IOException
+import java.net.Socket
+
+data class PadZone(val name: String, val x: Float, val y: Float, val width: Float, val height: Float)
+
+// Receives the bridge state pushed through `adb reverse tcp:PORT tcp:PORT`.
+// The wire format is described in src/control.rs.
+class ControlConnection(private val port: Int = DEFAULT_PORT) {
+    var forwarding by mutableStateOf(false)
+        private set
+    var monitor by mutableStateOf<String?>(null)
+        private set
+    var profile by mutableStateOf<String?>(null)
+        private set
+    var zones by mutableStateOf(listOf<PadZone>())
+        private set
+
+    @Volatile
+    private var running = false
+    private var socket: Socket? = null
+    private var thread: Thread? = null
+
+    fun start() {
+        running = true
+        if (thread?.isAlive == true) {
+            return
+        }
+        thread = Thread {
+            while (running) {
+                try {
+                    Socket("127.0.0.1", port).use {
+                        socket = it
+                        read(DataInputStream(BufferedInputStream(it.getInputStream())))
+                    }
+                } catch (e: IOException) {
+                }
+                forwarding = false
+                Thread.sleep(1000)
+            }
+        }.apply { start() }
+    }
+
+    fun stop() {
+        running = false
+        try {
+            socket?.close()
+        } catch (e: IOException) {
+        }
+    }
+
+    private fun read(input: DataInputStream) {
+        while (running) {
+            val kind = input.readUnsignedByte()
+            val payload = ByteArray(input.readInt())
+            input.readFully(payload)
+            val message = DataInputStream(payload.inputStream())
+
+            when (kind) {
+                MESSAGE_HELLO -> if (message.readUnsignedByte() > PROTOCOL_VERSION) {
+                    return
+                }
+                MESSAGE_FORWARDING -> forwarding = message.readUnsignedByte() != 0
+                MESSAGE_MONITOR -> monitor = message.readUTF()
+                MESSAGE_PROFILE -> profile = message.readUTF()
+                MESSAGE_ZONES -> zones = List(message.readUnsignedShort()) {
+                    PadZone(
+                        message.readUTF(),
+                        message.readFloat(),
+                        message.readFloat(),
+                        message.readFloat(),
+                        message.readFloat(),
+                    )
+                }
+            }
+        }
+    }
+
+    companion object {
+        const val DEFAULT_PORT = 27184
+        private const val PROTOCOL_VERSION = 1
+        private const val MESSAGE_HELLO = 1
+        private const val MESSAGE_FORWARDING = 2
+        private const val MESSAGE_MONITOR = 3
+        private const val MESSAGE_PROFILE = 4
+        private const val MESSAGE_ZONES = 5
+    }
+}
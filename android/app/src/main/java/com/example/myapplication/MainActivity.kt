@@ -1,6 +1,7 @@
 package com.example.myapplication
 
 import android.os.Bundle
+import android.view.MotionEvent
 import android.view.Window
 import android.view.WindowManager
 import androidx.activity.ComponentActivity
@@ -19,9 +20,15 @@ import androidx.core.view.WindowInsetsControllerCompat
 import com.example.myapplication.ui.theme.MyApplicationTheme
 
 class MainActivity : ComponentActivity() {
+    private lateinit var companion: CompanionConnection
+    private val control = ControlConnection()
+    private val sidecar = SidecarConnection()
+
     override fun onCreate(savedInstanceState: Bundle?) {
         super.onCreate(savedInstanceState)
 
+        companion = CompanionConnection(resources.displayMetrics)
+
         val windowInsetsController = WindowCompat.getInsetsController(window, window.decorView)
         windowInsetsController.systemBarsBehavior = WindowInsetsControllerCompat.BEHAVIOR_SHOW_TRANSIENT_BARS_BY_SWIPE
         windowInsetsController.hide(WindowInsetsCompat.Type.systemBars())
@@ -32,14 +39,39 @@ class MainActivity : ComponentActivity() {
         setContent {
             MyApplicationTheme {
                 Scaffold(modifier = Modifier.fillMaxSize()) { innerPadding ->
-                    Greeting(
-                        name = "Android",
+                    SidecarView(sidecar = sidecar)
+                    BridgeOverlay(
+                        control = control,
                         modifier = Modifier.padding(innerPadding)
                     )
                 }
             }
         }
     }
+
+    override fun onResume() {
+        super.onResume()
+        companion.start()
+        control.start()
+        sidecar.start()
+    }
+
+    override fun onPause() {
+        companion.stop()
+        control.stop()
+        sidecar.stop()
+        super.onPause()
+    }
+
+    override fun dispatchTouchEvent(ev: MotionEvent): Boolean {
+        companion.send(ev)
+        return super.dispatchTouchEvent(ev)
+    }
+
+    override fun dispatchGenericMotionEvent(ev: MotionEvent): Boolean {
+        companion.send(ev)
+        return super.dispatchGenericMotionEvent(ev)
+    }
 }
 
 @Composable
@@ -0,0 +1,104 @@
+package com.example.myapplication
+
+import android.graphics.Bitmap
+import android.graphics.BitmapFactory
+import android.graphics.Canvas
+import androidx.compose.runtime.getValue
+import androidx.compose.runtime.mutableIntStateOf
+import androidx.compose.runtime.setValue
+import java.io.BufferedInputStream
+import java.io.DataInputStream
+import java.io.IOException
+import java.net.Socket
+
+// Receives the host screen region streamed by `forward --sidecar`.
+// The wire format is described in src/sidecar.rs.
+class SidecarConnection(private val port: Int = DEFAULT_PORT) {
+    var bitmap: Bitmap? = null
+        private set
+    var frame by mutableIntStateOf(0)
+        private set
+
+    @Volatile
+    private var running = false
+    private var socket: Socket? = null
+    private var thread: Thread? = null
+
+    fun start() {
+        running = true
+        if (thread?.isAlive == true) {
+            return
+        }
+        thread = Thread {
+            while (running) {
+                try {
+                    Socket("127.0.0.1", port).use {
+                        socket = it
+                        read(DataInputStream(BufferedInputStream(it.getInputStream())))
+                    }
+                } catch (e: IOException) {
+                }
+                Thread.sleep(1000)
+            }
+        }.apply { start() }
+    }
+
+    fun stop() {
+        running = false
+        try {
+            socket?.close()
+        } catch (e: IOException) {
+        }
+    }
+
+    private fun read(input: DataInputStream) {
+        val magic = ByteArray(4)
+        input.readFully(magic)
+        if (String(magic, Charsets.US_ASCII) != "ATBS" || input.readUnsignedByte() != VERSION) {
+            return
+        }
+
+        val target = Bitmap.createBitmap(input.readUnsignedShort(), input.readUnsignedShort(), Bitmap.Config.ARGB_8888)
+        val canvas = Canvas(target)
+        bitmap = target
+
+        while (running) {
+            val tiles = input.readUnsignedShort()
+            synchronized(target) {
+                repeat(tiles) {
+                    val x = input.readUnsignedShort()
+                    val y = input.readUnsignedShort()
+                    val width = input.readUnsignedShort()
+                    val height = input.readUnsignedShort()
+                    val encoding = input.readUnsignedByte()
+                    val data = ByteArray(input.readInt())
+                    input.readFully(data)
+
+                    when (encoding) {
+                        TILE_RAW -> {
+                            val pixels = IntArray(width * height) { i ->
+                                val r = data[i * 3].toInt() and 0xff
+                                val g = data[i * 3 + 1].toInt() and 0xff
+                                val b = data[i * 3 + 2].toInt() and 0xff
+                                (0xff shl 24) or (r shl 16) or (g shl 8) or b
+                            }
+                            target.setPixels(pixels, 0, width, x, y, width, height)
+                        }
+                        TILE_JPEG -> BitmapFactory.decodeByteArray(data, 0, data.size)?.let {
+                            canvas.drawBitmap(it, x.toFloat(), y.toFloat(), null)
+                            it.recycle()
+                        }
+                    }
+                }
+            }
+            frame++
+        }
+    }
+
+    companion object {
+        const val DEFAULT_PORT = 27185
+        private const val VERSION = 1
+        private const val TILE_RAW = 0
+        private const val TILE_JPEG = 1
+    }
+}
@@ -0,0 +1,524 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use adb_client::{ADBDeviceExt, ADBTcpDevice};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::{debug, info};
+use rsa::{
+    pkcs8::{DecodePrivateKey, EncodePrivateKey, LineEnding},
+    traits::PublicKeyParts,
+    BigUint, Pkcs1v15Sign, RsaPrivateKey,
+};
+
+use crate::{error::Error, source::EventSource};
+
+const A_CNXN: u32 = 0x4e584e43;
+const A_AUTH: u32 = 0x48545541;
+const A_OPEN: u32 = 0x4e45504f;
+const A_OKAY: u32 = 0x59414b4f;
+const A_CLSE: u32 = 0x45534c43;
+const A_WRTE: u32 = 0x45545257;
+const A_STLS: u32 = 0x534c5453;
+
+const A_VERSION: u32 = 0x01000001;
+const MAX_PAYLOAD: u32 = 256 * 1024;
+
+const AUTH_TOKEN: u32 = 1;
+const AUTH_SIGNATURE: u32 = 2;
+const AUTH_RSAPUBLICKEY: u32 = 3;
+
+const KEY_BITS: usize = 2048;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, PartialEq)]
+struct Message {
+    command: u32,
+    arg0: u32,
+    arg1: u32,
+    data: Vec<u8>,
+}
+
+impl Message {
+    fn new(command: u32, arg0: u32, arg1: u32, data: Vec<u8>) -> Self {
+        Message {
+            command,
+            arg0,
+            arg1,
+            data,
+        }
+    }
+}
+
+fn write_message(stream: &mut impl Write, message: &Message) -> io::Result<()> {
+    let checksum = message
+        .data
+        .iter()
+        .fold(0u32, |sum, x| sum.wrapping_add(*x as u32));
+
+    let mut header = Vec::with_capacity(24);
+    for value in [
+        message.command,
+        message.arg0,
+        message.arg1,
+        message.data.len() as u32,
+        checksum,
+        !message.command,
+    ] {
+        header.extend_from_slice(&value.to_le_bytes());
+    }
+
+    stream.write_all(&header)?;
+    stream.write_all(&message.data)?;
+    stream.flush()
+}
+
+fn read_message(stream: &mut impl Read) -> io::Result<Message> {
+    let mut header = [0u8; 24];
+    stream.read_exact(&mut header)?;
+
+    let field = |index: usize| {
+        u32::from_le_bytes(
+            header[index * 4..index * 4 + 4]
+                .try_into()
+                .unwrap_or([0; 4]),
+        )
+    };
+    let (command, arg0, arg1, length, magic) = (field(0), field(1), field(2), field(3), field(5));
+
+    if magic != !command {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid ADB message magic for command {:08x}", command),
+        ));
+    }
+
+    // The length comes from the peer, it is checked before allocating
+    if length > MAX_PAYLOAD {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("ADB message payload of {} bytes is too large", length),
+        ));
+    }
+
+    let mut data = vec![0u8; length as usize];
+    stream.read_exact(&mut data)?;
+
+    Ok(Message::new(command, arg0, arg1, data))
+}
+
+pub struct AdbKey {
+    private_key: RsaPrivateKey,
+    public_key: String,
+}
+
+impl AdbKey {
+    // Loads the key shared with the adb tools, creating one the same way adb does when missing.
+    pub fn load_or_generate(path: &Path) -> Result<Self, Error> {
+        let public_path = PathBuf::from(format!("{}.pub", path.display()));
+
+        if !path.exists() {
+            info!("Generating ADB key {}", path.display());
+            let private_key = RsaPrivateKey::new(&mut rand::rngs::OsRng, KEY_BITS)
+                .map_err(|err| Error::Protocol(err.to_string()))?;
+            let pem = private_key
+                .to_pkcs8_pem(LineEnding::LF)
+                .map_err(|err| Error::Protocol(err.to_string()))?;
+
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // Only readable by the user like adb does
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(path)?
+                .write_all(pem.as_bytes())?;
+        }
+
+        let key = AdbKey::from_pem(&fs::read_to_string(path)?)?;
+
+        if !public_path.exists() {
+            fs::write(&public_path, format!("{}\n", key.public_key))?;
+        }
+
+        Ok(key)
+    }
+
+    pub fn from_pem(pem: &str) -> Result<Self, Error> {
+        let private_key = RsaPrivateKey::from_pkcs8_pem(pem)
+            .map_err(|err| Error::parse("ADB private key", err))?;
+        let public_key = format!(
+            "{} android-tablet-bridge@{}",
+            STANDARD.encode(android_public_key(&private_key)),
+            hostname()
+        );
+
+        Ok(AdbKey {
+            private_key,
+            public_key,
+        })
+    }
+
+    // The token is signed as if it were a SHA-1 digest, like adbd expects.
+    fn sign(&self, token: &[u8]) -> Result<Vec<u8>, Error> {
+        self.private_key
+            .sign(Pkcs1v15Sign::new::<sha1::Sha1>(), token)
+            .map_err(|err| Error::Protocol(format!("Could not sign ADB token: {}", err)))
+    }
+}
+
+pub fn default_key_path() -> PathBuf {
+    let android_home = std::env::var_os("ANDROID_USER_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(".android")
+        });
+
+    android_home.join("adbkey")
+}
+
+// Layout of the RSAPublicKey structure from android_pubkey.h, with Montgomery parameters.
+fn android_public_key(private_key: &RsaPrivateKey) -> Vec<u8> {
+    let words = KEY_BITS / 32;
+    let modulus = private_key.n();
+
+    let mut modulus_bytes = modulus.to_bytes_le();
+    modulus_bytes.resize(words * 4, 0);
+
+    let n0 = u32::from_le_bytes([
+        modulus_bytes[0],
+        modulus_bytes[1],
+        modulus_bytes[2],
+        modulus_bytes[3],
+    ]);
+    let mut inverse: u32 = 1;
+    for _ in 0..5 {
+        inverse = inverse.wrapping_mul(2u32.wrapping_sub(n0.wrapping_mul(inverse)));
+    }
+
+    let mut rr_bytes = ((BigUint::from(1u8) << (KEY_BITS * 2)) % modulus).to_bytes_le();
+    rr_bytes.resize(words * 4, 0);
+
+    let mut exponent_bytes = private_key.e().to_bytes_le();
+    exponent_bytes.resize(4, 0);
+
+    let mut output = Vec::with_capacity(words * 8 + 12);
+    output.extend_from_slice(&(words as u32).to_le_bytes());
+    output.extend_from_slice(&inverse.wrapping_neg().to_le_bytes());
+    output.extend_from_slice(&modulus_bytes);
+    output.extend_from_slice(&rr_bytes);
+    output.extend_from_slice(&exponent_bytes[..4]);
+    output
+}
+
+fn hostname() -> String {
+    fs::read_to_string("/etc/hostname")
+        .map(|x| x.trim().to_string())
+        .ok()
+        .filter(|x| !x.is_empty())
+        .unwrap_or_else(|| String::from("localhost"))
+}
+
+pub struct NativeAdbConnection {
+    stream: TcpStream,
+}
+
+enum Handshake {
+    Connected(NativeAdbConnection),
+    TlsRequired,
+}
+
+impl NativeAdbConnection {
+    fn connect(address: &str, key: &AdbKey) -> Result<Handshake, Error> {
+        let socket_address = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| Error::NotFound(format!("Could not resolve {}", address)))?;
+        let mut stream = TcpStream::connect_timeout(&socket_address, CONNECT_TIMEOUT)?;
+        stream.set_nodelay(true)?;
+
+        write_message(
+            &mut stream,
+            &Message::new(A_CNXN, A_VERSION, MAX_PAYLOAD, b"host::\0".to_vec()),
+        )?;
+
+        let mut signed = false;
+        let mut sent_public_key = false;
+
+        loop {
+            let message = read_message(&mut stream)?;
+
+            match message.command {
+                A_CNXN => {
+                    debug!(
+                        "Connected to {}: {}",
+                        address,
+                        String::from_utf8_lossy(&message.data).trim_end_matches('\0')
+                    );
+                    return Ok(Handshake::Connected(NativeAdbConnection { stream }));
+                }
+                A_STLS => return Ok(Handshake::TlsRequired),
+                A_AUTH if message.arg0 == AUTH_TOKEN && !signed => {
+                    let signature = key.sign(&message.data)?;
+                    write_message(
+                        &mut stream,
+                        &Message::new(A_AUTH, AUTH_SIGNATURE, 0, signature),
+                    )?;
+                    signed = true;
+                }
+                // A second token means the signature was refused, the key is then offered.
+                A_AUTH if message.arg0 == AUTH_TOKEN && !sent_public_key => {
+                    info!("Accept the debugging prompt on the device to authorize this host");
+                    let mut public_key = key.public_key.clone().into_bytes();
+                    public_key.push(0);
+                    write_message(
+                        &mut stream,
+                        &Message::new(A_AUTH, AUTH_RSAPUBLICKEY, 0, public_key),
+                    )?;
+                    sent_public_key = true;
+                }
+                A_AUTH => {
+                    return Err(Error::Protocol(String::from(
+                        "The device refused this host's ADB key",
+                    )))
+                }
+                command => {
+                    return Err(Error::Protocol(format!(
+                        "Unexpected {:08x} message during connection",
+                        command
+                    )))
+                }
+            }
+        }
+    }
+
+    pub fn shell(&mut self, command: &str, output: &mut dyn Write) -> Result<(), Error> {
+        let local_id = 1;
+        let mut service = format!("shell:{}", command).into_bytes();
+        service.push(0);
+        write_message(
+            &mut self.stream,
+            &Message::new(A_OPEN, local_id, 0, service),
+        )?;
+
+        let mut remote_id = None;
+
+        loop {
+            let message = read_message(&mut self.stream)?;
+
+            match message.command {
+                A_OKAY => remote_id = Some(message.arg0),
+                A_WRTE => {
+                    let remote = message.arg0;
+                    remote_id = Some(remote);
+                    output.write_all(&message.data)?;
+                    write_message(
+                        &mut self.stream,
+                        &Message::new(A_OKAY, local_id, remote, vec![]),
+                    )?;
+                }
+                A_CLSE => {
+                    match remote_id {
+                        None => {
+                            return Err(Error::Protocol(format!(
+                                "The device refused to open {}",
+                                command
+                            )))
+                        }
+                        Some(remote) => {
+                            let _ = write_message(
+                                &mut self.stream,
+                                &Message::new(A_CLSE, local_id, remote, vec![]),
+                            );
+                        }
+                    }
+                    return Ok(output.flush()?);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+pub struct NativeAdbSource {
+    address: String,
+    key: AdbKey,
+    key_path: PathBuf,
+}
+
+impl NativeAdbSource {
+    pub fn new(address: String, key: AdbKey, key_path: PathBuf) -> Self {
+        NativeAdbSource {
+            address,
+            key,
+            key_path,
+        }
+    }
+}
+
+// adb_client only opens TLS connections with this key and cannot be given another one
+fn tls_key_path() -> PathBuf {
+    PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(".android/adbkey")
+}
+
+impl EventSource for NativeAdbSource {
+    fn name(&self) -> String {
+        self.address.clone()
+    }
+
+    fn shell(&self, command: &[&str], output: &mut dyn Write) -> Result<(), Error> {
+        match NativeAdbConnection::connect(&self.address, &self.key)? {
+            Handshake::Connected(mut connection) => connection.shell(&command.join(" "), output),
+            // Wireless debugging ports of Android 11+ require TLS with a previously paired key.
+            Handshake::TlsRequired => {
+                if self.key_path != tls_key_path() {
+                    return Err(Error::InvalidArguments(format!(
+                        "{} requires TLS, which only works with the key at {}, not {}",
+                        self.address,
+                        tls_key_path().display(),
+                        self.key_path.display()
+                    )));
+                }
+
+                let socket_address = self.address.to_socket_addrs()?.next().ok_or_else(|| {
+                    Error::NotFound(format!("Could not resolve {}", self.address))
+                })?;
+                let mut device = ADBTcpDevice::new(socket_address)?;
+                device.shell_command(command.iter(), output)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rsa::RsaPublicKey;
+    use std::net::TcpListener;
+
+    // Minimal adbd refusing the first signature to exercise the public key path.
+    fn standin_adbd(listener: TcpListener, public_key: RsaPublicKey, response: &'static [u8]) {
+        let (mut stream, _) = listener.accept().unwrap();
+
+        let connect = read_message(&mut stream).unwrap();
+        assert_eq!(connect.command, A_CNXN);
+        assert_eq!(connect.data, b"host::\0".to_vec());
+
+        let token = vec![7u8; 20];
+        write_message(
+            &mut stream,
+            &Message::new(A_AUTH, AUTH_TOKEN, 0, token.clone()),
+        )
+        .unwrap();
+
+        let signature = read_message(&mut stream).unwrap();
+        assert_eq!(
+            (signature.command, signature.arg0),
+            (A_AUTH, AUTH_SIGNATURE)
+        );
+        public_key
+            .verify(Pkcs1v15Sign::new::<sha1::Sha1>(), &token, &signature.data)
+            .unwrap();
+        write_message(&mut stream, &Message::new(A_AUTH, AUTH_TOKEN, 0, token)).unwrap();
+
+        let key_message = read_message(&mut stream).unwrap();
+        assert_eq!(
+            (key_message.command, key_message.arg0),
+            (A_AUTH, AUTH_RSAPUBLICKEY)
+        );
+        let encoded = String::from_utf8(key_message.data).unwrap();
+        let decoded = STANDARD.decode(encoded.split(' ').next().unwrap()).unwrap();
+        assert_eq!(&decoded[0..4], &64u32.to_le_bytes());
+        assert_eq!(
+            BigUint::from_bytes_le(&decoded[8..264]),
+            public_key.n().clone()
+        );
+
+        write_message(
+            &mut stream,
+            &Message::new(
+                A_CNXN,
+                A_VERSION,
+                4096,
+                b"device::ro.product.name=test;\0".to_vec(),
+            ),
+        )
+        .unwrap();
+
+        let open = read_message(&mut stream).unwrap();
+        assert_eq!(open.command, A_OPEN);
+        assert_eq!(open.data, b"shell:getevent -i\0".to_vec());
+        let local_id = open.arg0;
+
+        write_message(&mut stream, &Message::new(A_OKAY, 42, local_id, vec![])).unwrap();
+        for chunk in response.chunks(64) {
+            write_message(
+                &mut stream,
+                &Message::new(A_WRTE, 42, local_id, chunk.to_vec()),
+            )
+            .unwrap();
+            let okay = read_message(&mut stream).unwrap();
+            assert_eq!((okay.command, okay.arg0, okay.arg1), (A_OKAY, local_id, 42));
+        }
+        write_message(&mut stream, &Message::new(A_CLSE, 42, local_id, vec![])).unwrap();
+        assert_eq!(read_message(&mut stream).unwrap().command, A_CLSE);
+    }
+
+    #[test]
+    fn it_authenticates_and_runs_shell_commands() {
+        let key = AdbKey::from_pem(include_str!("../fixtures/test-adbkey.pem")).unwrap();
+        let public_key = key.private_key.to_public_key();
+        let response = include_str!("../devices.txt").as_bytes();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let adbd = std::thread::spawn(move || standin_adbd(listener, public_key, response));
+
+        let source = NativeAdbSource::new(address, key, PathBuf::from("fixtures/test-adbkey.pem"));
+        let subdevices = source.describe().unwrap();
+        adbd.join().unwrap();
+
+        assert!(subdevices.iter().any(|x| x.name == "sec_e-pen"));
+    }
+
+    #[test]
+    fn it_refuses_tls_with_another_key() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let adbd = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            assert_eq!(read_message(&mut stream).unwrap().command, A_CNXN);
+            write_message(&mut stream, &Message::new(A_STLS, 0x01000000, 0, vec![])).unwrap();
+        });
+
+        let source = NativeAdbSource::new(
+            address,
+            AdbKey::from_pem(include_str!("../fixtures/test-adbkey.pem")).unwrap(),
+            PathBuf::from("fixtures/test-adbkey.pem"),
+        );
+        let result = source.describe();
+        adbd.join().unwrap();
+
+        assert!(matches!(result, Err(Error::InvalidArguments(_))));
+    }
+
+    #[test]
+    fn it_rejects_oversized_messages() {
+        let mut header = vec![];
+        for value in [A_WRTE, 1, 1, u32::MAX, 0, !A_WRTE] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let err = read_message(&mut header.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,157 @@
+use clap::ValueEnum;
+use input_linux::{AbsoluteAxis, InputProperty, Key};
+
+use crate::parser::ADBDevice;
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum DeviceKind {
+    Pen,
+    Touchscreen,
+    Touchpad,
+    Keyboard,
+    Gamepad,
+    // Buttons which are not a keyboard, like power and volume keys
+    Keys,
+}
+
+const PEN_TOOLS: [Key; 4] = [
+    Key::ButtonToolPen,
+    Key::ButtonToolBrush,
+    Key::ButtonToolPencil,
+    Key::ButtonToolAirbrush,
+];
+
+const KEYBOARD_KEYS: [Key; 6] = [Key::A, Key::Q, Key::Z, Key::Num1, Key::Space, Key::Enter];
+
+const GAMEPAD_BUTTONS: [Key; 4] = [
+    Key::ButtonSouth,
+    Key::ButtonEast,
+    Key::ButtonThumbl,
+    Key::ButtonStart,
+];
+
+// Scores how much the capabilities of a subdevice look like each kind, a kind scoring 0 is ruled
+// out. Kinds are scored independently so a touchscreen with a wakeup key still scores as keys.
+pub fn scores(device: &ADBDevice) -> Vec<(DeviceKind, u32)> {
+    let has_key = |key: Key| device.events.keys.contains(&key);
+    let has_axis = |axis: AbsoluteAxis| device.events.absolute.iter().any(|x| x.axis == axis);
+    let has_property = |property: InputProperty| device.properties.contains(&property);
+    let count = |keys: &[Key]| keys.iter().filter(|x| has_key(**x)).count() as u32;
+
+    let positioned = has_axis(AbsoluteAxis::X) && has_axis(AbsoluteAxis::Y);
+    let multitouch =
+        has_axis(AbsoluteAxis::MultitouchPositionX) && has_axis(AbsoluteAxis::MultitouchPositionY);
+    let pen = positioned && count(&PEN_TOOLS) > 0;
+    let finger = !pen && (multitouch || positioned && has_key(Key::ButtonTouch));
+    // Like Android, touch devices are touchscreens unless they look like a pointer
+    let direct = has_property(InputProperty::Direct);
+    let pointer = has_property(InputProperty::Pointer)
+        || has_property(InputProperty::ButtonPad)
+        || has_key(Key::ButtonLeft);
+    let touch_score = 6 + 4 * multitouch as u32 + 2 * has_axis(AbsoluteAxis::MultitouchSlot) as u32;
+
+    let pen_score = match pen {
+        false => 0,
+        true => {
+            10 + 3 * has_key(Key::ButtonStylus) as u32
+                + 2 * has_axis(AbsoluteAxis::Pressure) as u32
+                + 2 * has_axis(AbsoluteAxis::TiltX) as u32
+                + has_key(Key::ButtonToolRubber) as u32
+        }
+    };
+
+    let touchscreen_score = match finger && (direct || !pointer) {
+        false => 0,
+        true => touch_score + 4 * direct as u32,
+    };
+
+    let touchpad_score = match finger && pointer && !direct {
+        false => 0,
+        true => touch_score + 4,
+    };
+
+    let keyboard_score = match count(&KEYBOARD_KEYS) {
+        0..=3 => 0,
+        matched => 2 * matched,
+    };
+
+    let gamepad_score = match count(&GAMEPAD_BUTTONS) {
+        0 => 0,
+        matched => 6 + matched + 2 * has_axis(AbsoluteAxis::Hat0X) as u32,
+    };
+
+    let keys_score = device
+        .events
+        .keys
+        .iter()
+        .filter(|x| !is_button(**x))
+        .count()
+        .min(5) as u32;
+
+    vec![
+        (DeviceKind::Pen, pen_score),
+        (DeviceKind::Touchscreen, touchscreen_score),
+        (DeviceKind::Touchpad, touchpad_score),
+        (DeviceKind::Keyboard, keyboard_score),
+        (DeviceKind::Gamepad, gamepad_score),
+        (DeviceKind::Keys, keys_score),
+    ]
+}
+
+// The best scoring kind, earlier kinds winning ties. None for sensors, switches and the like.
+pub fn classify(device: &ADBDevice) -> Option<DeviceKind> {
+    scores(device)
+        .into_iter()
+        .rev()
+        .filter(|(_, score)| *score > 0)
+        .max_by_key(|(_, score)| *score)
+        .map(|(kind, _)| kind)
+}
+
+// BTN_MISC..BTN_GEAR_UP and BTN_TRIGGER_HAPPY ranges
+fn is_button(key: Key) -> bool {
+    let code = key as u16;
+    (0x100..0x160).contains(&code) || (0x2c0..0x300).contains(&code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_devices, parse_devices_lenient};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_classifies_subdevices() {
+        let devices = parse_devices(include_str!("../devices.txt")).unwrap();
+        let (labelled, _) = parse_devices_lenient(include_str!("../devices-labelled.txt"));
+
+        let classified: Vec<(&str, Option<DeviceKind>)> = devices
+            .iter()
+            .chain(labelled.iter())
+            .map(|x| (x.name.as_str(), classify(x)))
+            .collect();
+
+        assert_eq!(
+            classified,
+            vec![
+                ("grip_notifier", None),
+                ("grip_sensor_wifi", None),
+                ("grip_notifier", None),
+                ("grip_sensor", None),
+                ("sec_e-pen", Some(DeviceKind::Pen)),
+                ("hall", None),
+                ("hall_wacom", None),
+                ("sec_touchpad", Some(DeviceKind::Touchpad)),
+                ("sec_touchscreen", Some(DeviceKind::Touchscreen)),
+                ("hall_logical", None),
+                ("meta_event", None),
+                ("gpio-keys", Some(DeviceKind::Keys)),
+                ("mtk-pmic-keys", Some(DeviceKind::Keys)),
+                ("sec_e-pen", Some(DeviceKind::Pen)),
+                ("gpio_keys", Some(DeviceKind::Keys)),
+                ("virtual_sensor", None),
+                ("legacy_touch", Some(DeviceKind::Touchscreen)),
+            ]
+        );
+    }
+}
@@ -1,63 +1,296 @@
 use std::{
-    fs::{File, OpenOptions},
-    io::{self, BufRead, BufReader, Read},
+    fs::{self, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4, TcpStream},
     os::unix::{fs::OpenOptionsExt, net::UnixStream},
+    path::PathBuf,
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
-use adb_client::{ADBDeviceExt, ADBServer, ADBServerDevice};
+use adb_client::ADBServer;
 use clap::{Parser, ValueEnum};
 use cli_table::{print_stdout, Cell, Style, Table};
 use input_linux::{
-    sys::uinput_abs_setup, AbsoluteAxis, Event, EventKind, InputId, Key, UInputHandle,
+    sys::uinput_abs_setup, AbsoluteAxis, Event, EventKind, EventTime, InputId, InputProperty, Key,
+    RelativeAxis, UInputHandle,
 };
+use log::{error, info, warn, LevelFilter};
 use nix::libc::O_NONBLOCK;
 
-use crate::parser::{parse_devices, parse_input_event, ADBDevice};
+use crate::{
+    classify::{classify, DeviceKind},
+    control::{
+        read_message, ControlMessage, ControlServer, ControlState, PadZone, PROTOCOL_VERSION,
+    },
+    discovery::{discover, CONNECT_SERVICE, PAIRING_SERVICE},
+    doctor::{group_exists, run_checks, udev_rule, Status},
+    eraser::{EraserSwitch, EraserTrigger},
+    error::Error,
+    gesture::{perform_gesture_action, Gesture, GestureAction, GestureBinding, GestureRecognizer},
+    identity::{hwdb_file, libwacom_tablet_file, DeviceIdentity, IdentityArgs},
+    inspect::Inspector,
+    listing::{format_entries, DeviceEntry, OutputFormat, SubdeviceEntry},
+    logging::{self, LogFormat},
+    metrics::{serve_metrics, Metrics},
+    output::{open_dry_run_output, OutputDevice, SharedWriter},
+    palm::PalmRejection,
+    parser::{parse_input_event, ADBDevice},
+    pen::PenTracker,
+    relative::RelativeMotion,
+    sidecar::{InputAlignment, SidecarArgs},
+    signals,
+    source::{SharedSource, SourceArgs},
+    stats::{estimate_clock_offset, Stats},
+    tilt::TiltArgs,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+pub const UINPUT_PATH: &str = "/dev/uinput";
+pub const UINPUT_PERMISSION_HINT: &str =
+    "run `sudo android-tablet-bridge install-udev-rules`, add your user to the uinput group and log in again";
 
 #[derive(Parser, Debug)]
-enum AppCli {
-    ListDevice,
+struct AppCli {
+    #[arg(long, global = true, default_value_t = LevelFilter::Info)]
+    log_level: LevelFilter,
+
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Auto)]
+    log_format: LogFormat,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    ListDevice(ListDeviceArgs),
     ListSubDevice(ListSubDeviceArgs),
-    Forward(ForwardArgs),
+    Forward(Box<ForwardArgs>),
+    Inspect(InspectArgs),
+    GenerateIdentity(GenerateIdentityArgs),
+    Doctor,
+    InstallUdevRules(InstallUdevRulesArgs),
+    Discover(DiscoverArgs),
+    Pair(PairArgs),
+    ControlClient(ControlClientArgs),
+}
+
+#[derive(clap::Args, Debug)]
+#[command(version, about, long_about = None)]
+struct DiscoverArgs {
+    #[arg(long, value_name = "SECONDS", default_value_t = 3)]
+    timeout: u64,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(version, about, long_about = None)]
+struct ControlClientArgs {
+    #[arg(long, default_value = "127.0.0.1:27184")]
+    address: SocketAddr,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(version, about, long_about = None)]
+struct PairArgs {
+    #[arg(long, value_name = "IP:PORT")]
+    address: Option<SocketAddrV4>,
+
+    #[arg(long)]
+    code: String,
+
+    #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+    timeout: u64,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(version, about, long_about = None)]
+struct InstallUdevRulesArgs {
+    // Not the input group, its members can read every keyboard on the host
+    #[arg(long, default_value = "uinput")]
+    group: String,
+
+    #[arg(
+        long,
+        default_value = "/etc/udev/rules.d/60-android-tablet-bridge.rules"
+    )]
+    output: PathBuf,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(version, about, long_about = None)]
+struct ListDeviceArgs {
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
 }
 
 #[derive(clap::Args, Debug)]
 #[command(version, about, long_about = None)]
 struct ListSubDeviceArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(version, about, long_about = None)]
+struct InspectArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+
     #[arg(long)]
-    device: String,
+    subdevice: Option<String>,
+
+    #[arg(long, default_value_t = 50)]
+    refresh_ms: u64,
 }
 
 #[derive(clap::Args, Debug)]
 #[command(version, about, long_about = None)]
-struct ForwardArgs {
+struct GenerateIdentityArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+
+    #[arg(long)]
+    subdevice: Option<String>,
+
     #[arg(long)]
-    device: Option<String>,
+    name: Option<String>,
+
+    #[command(flatten)]
+    identity: IdentityArgs,
+
+    #[arg(long, default_value_t = 10)]
+    fallback_resolution: i32,
+
+    #[arg(long, default_value = ".")]
+    output_dir: PathBuf,
+
+    #[arg(long)]
+    eraser: bool,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(version, about, long_about = None)]
+struct ForwardArgs {
+    #[command(flatten)]
+    source: SourceArgs,
 
     #[arg(long)]
     subdevice: Option<String>,
 
-    #[arg(long, default_value_t = String::from("Android Tablet Bridge"))]
-    name: String,
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "pen")]
+    kind: Vec<DeviceKind>,
+
+    #[arg(long)]
+    name: Option<String>,
+
+    #[command(flatten)]
+    identity: IdentityArgs,
 
     #[arg(long)]
     rotation: Option<Rotation>,
 
+    #[arg(long, value_enum, default_value_t = Mode::Absolute)]
+    mode: Mode,
+
+    #[arg(long, default_value_t = 0.1)]
+    relative_speed: f64,
+
+    #[arg(long, default_value_t = 0.0)]
+    relative_acceleration: f64,
+
     #[arg(long, default_value_t = 10)]
     fallback_resolution: i32,
+
+    #[arg(long = "gesture", value_name = "GESTURE=ACTION")]
+    gestures: Vec<GestureBinding>,
+
+    #[arg(long)]
+    touch_subdevice: Option<String>,
+
+    #[arg(long)]
+    forward_touch: bool,
+
+    #[arg(long, default_value_t = 500)]
+    palm_grace_ms: u64,
+
+    #[arg(long)]
+    palm_max_touch_major: Option<i32>,
+
+    #[arg(long, value_name = "SECONDS")]
+    stats_interval: Option<u64>,
+
+    #[arg(long, value_name = "ADDRESS")]
+    metrics_listen: Option<SocketAddr>,
+
+    #[arg(long)]
+    reconnect: bool,
+
+    #[arg(long, value_name = "FILE", num_args = 0..=1, default_missing_value = "-")]
+    dry_run: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PORT",
+        num_args = 0..=1,
+        default_missing_value = "27184"
+    )]
+    control: Option<u16>,
+
+    #[arg(long, requires = "control")]
+    monitor: Option<String>,
+
+    #[arg(long, requires = "control")]
+    profile: Option<String>,
+
+    #[arg(long = "pad-zone", value_name = "NAME=X,Y,WIDTH,HEIGHT")]
+    pad_zones: Vec<PadZone>,
+
+    #[arg(long, value_name = "TRIGGER")]
+    eraser: Option<EraserTrigger>,
+
+    #[command(flatten)]
+    tilt: TiltArgs,
+
+    #[command(flatten)]
+    sidecar: SidecarArgs,
 }
 
-pub fn run() -> Result<(), io::Error> {
-    match AppCli::parse() {
-        AppCli::ListDevice => list_device(),
-        AppCli::ListSubDevice(command_args) => list_subdevice(command_args),
-        AppCli::Forward(command_args) => forward(command_args),
+pub fn run() -> Result<(), Error> {
+    let app_cli = AppCli::parse();
+    logging::init(app_cli.log_level, app_cli.log_format);
+
+    match app_cli.command {
+        Command::ListDevice(command_args) => list_device(command_args),
+        Command::ListSubDevice(command_args) => list_subdevice(command_args),
+        Command::Forward(command_args) => forward(*command_args),
+        Command::Inspect(command_args) => inspect(command_args),
+        Command::GenerateIdentity(command_args) => generate_identity(command_args),
+        Command::Doctor => doctor(),
+        Command::InstallUdevRules(command_args) => install_udev_rules(command_args),
+        Command::Discover(command_args) => discover_devices(command_args),
+        Command::Pair(command_args) => pair(command_args),
+        Command::ControlClient(command_args) => control_client(command_args),
     }
 }
 
-fn list_device() -> Result<(), io::Error> {
+fn list_device(args: ListDeviceArgs) -> Result<(), Error> {
     let mut server = ADBServer::default();
-    let devices = server.devices_long().unwrap();
+    let devices = server.devices_long()?;
+
+    if let Some(format) = args.format.serialized() {
+        let entries: Vec<DeviceEntry> = devices.into_iter().map(DeviceEntry::from).collect();
+        print!("{}", format_entries(format, &entries)?);
+        return Ok(());
+    }
+
     let mut lines = vec![];
 
     for device in devices {
@@ -81,26 +314,30 @@ fn list_device() -> Result<(), io::Error> {
         ])
         .bold(true);
 
-    print_stdout(table)
+    Ok(print_stdout(table)?)
 }
 
-fn list_subdevice(args: ListSubDeviceArgs) -> Result<(), io::Error> {
-    let mut server = ADBServer::default();
-    let mut server_device = server
-        .get_device_by_name(&args.device)
-        .expect("Could not get device");
-
-    let (write_end, read_end) = UnixStream::pair().unwrap();
-
-    server_device
-        .shell_command(["getevent", "-p"], write_end)
-        .unwrap();
+fn list_subdevice(args: ListSubDeviceArgs) -> Result<(), Error> {
+    let sources = args.source.sources()?;
+    let source = match sources.as_slice() {
+        [source] => source,
+        _ => {
+            return Err(Error::InvalidArguments(String::from(
+                "Select a device with --device or --connect",
+            )))
+        }
+    };
 
-    let mut reader = BufReader::new(read_end);
-    let mut response = String::new();
-    reader.read_to_string(&mut response).expect("toc");
+    let devices = source.describe()?;
 
-    let (_, devices) = parse_devices(&response).expect("Could not parse device info");
+    if let Some(format) = args.format.serialized() {
+        let entries: Vec<SubdeviceEntry> = devices
+            .iter()
+            .map(|x| SubdeviceEntry::new(source.name(), x))
+            .collect();
+        print!("{}", format_entries(format, &entries)?);
+        return Ok(());
+    }
 
     let mut lines = vec![];
 
@@ -112,9 +349,30 @@ fn list_subdevice(args: ListSubDeviceArgs) -> Result<(), io::Error> {
             .map(|x| format!("{:?}", x))
             .collect();
 
+        let kind = classify(&device)
+            .and_then(|x| x.to_possible_value())
+            .map(|x| x.get_name().to_string())
+            .unwrap_or_default();
+
+        let (input_id, location, unique_id) = match device.id {
+            Some(id) => (
+                format!(
+                    "{:04x}:{:04x}:{:04x} v{:04x}",
+                    id.bustype, id.vendor, id.product, id.version
+                ),
+                id.location,
+                id.unique_id,
+            ),
+            None => (String::new(), String::new(), String::new()),
+        };
+
         lines.push(vec![
             device.name.cell(),
             device.path.cell(),
+            kind.cell(),
+            input_id.cell(),
+            location.cell(),
+            unique_id.cell(),
             keys.join(", ").cell(),
         ])
     }
@@ -124,146 +382,883 @@ fn list_subdevice(args: ListSubDeviceArgs) -> Result<(), io::Error> {
         .title(vec![
             "Identifier".cell().bold(true),
             "Path".cell().bold(true),
+            "Kind".cell().bold(true),
+            "Input id".cell().bold(true),
+            "Location".cell().bold(true),
+            "Unique id".cell().bold(true),
             "Keys".cell().bold(true),
         ])
         .bold(true);
 
-    print_stdout(table)
+    Ok(print_stdout(table)?)
+}
+
+fn generate_identity(args: GenerateIdentityArgs) -> Result<(), Error> {
+    let identify_args = IdentityTabletDeviceArgs::new(&args.source, args.subdevice)?;
+
+    let (_, mut device) = identify_tablet_device(identify_args)?.ok_or_else(tablet_not_found)?;
+    let identity = args.identity.resolve(args.name, &device)?;
+
+    if args.eraser && !device.events.keys.contains(&Key::ButtonToolRubber) {
+        device.events.keys.push(Key::ButtonToolRubber);
+    }
+
+    let tablet_path = args.output_dir.join("android-tablet-bridge.tablet");
+    let hwdb_path = args.output_dir.join("66-android-tablet-bridge.hwdb");
+
+    fs::write(
+        &tablet_path,
+        libwacom_tablet_file(&identity, &device, args.fallback_resolution),
+    )?;
+    fs::write(&hwdb_path, hwdb_file(&identity))?;
+
+    println!(
+        "Wrote {} and {}",
+        tablet_path.display(),
+        hwdb_path.display()
+    );
+    println!("Install them with:");
+    println!(
+        "  sudo install -Dm644 {} /etc/libwacom/android-tablet-bridge.tablet",
+        tablet_path.display()
+    );
+    println!(
+        "  sudo install -Dm644 {} /etc/udev/hwdb.d/66-android-tablet-bridge.hwdb",
+        hwdb_path.display()
+    );
+    println!("  sudo systemd-hwdb update && sudo udevadm trigger");
+    println!("Then run forward with the same identity options.");
+
+    Ok(())
 }
 
-fn forward(args: ForwardArgs) -> Result<(), io::Error> {
-    let identify_args = match (args.device, args.subdevice) {
-        (Some(device), Some(subdevice)) => {
-            IdentityTabletDeviceArgs::DeviceAndSubdevice(device, subdevice)
+fn discover_devices(args: DiscoverArgs) -> Result<(), Error> {
+    let devices = discover(
+        &[CONNECT_SERVICE, PAIRING_SERVICE],
+        Duration::from_secs(args.timeout),
+    )?;
+    let mut lines = vec![];
+
+    for device in devices {
+        let service = if device.service == PAIRING_SERVICE {
+            "pairing"
+        } else {
+            "connect"
+        };
+
+        lines.push(vec![
+            device.name.cell(),
+            service.cell(),
+            device.address.cell(),
+        ])
+    }
+
+    let table = lines
+        .table()
+        .title(vec![
+            "Name".cell().bold(true),
+            "Service".cell().bold(true),
+            "Address".cell().bold(true),
+        ])
+        .bold(true);
+
+    Ok(print_stdout(table)?)
+}
+
+fn pair(args: PairArgs) -> Result<(), Error> {
+    let address = match args.address {
+        Some(address) => address,
+        None => {
+            info!("Looking for a device in pairing mode");
+            let devices = discover(&[PAIRING_SERVICE], Duration::from_secs(args.timeout))?;
+            match devices.as_slice() {
+                [device] => device.address,
+                [] => {
+                    return Err(Error::NotFound(String::from(
+                        "No device in pairing mode found, open \"Pair device with pairing code\" in the wireless debugging settings or pass --address",
+                    )))
+                }
+                _ => {
+                    return Err(Error::InvalidArguments(String::from(
+                        "Several devices are in pairing mode, select one with --address",
+                    )))
+                }
+            }
         }
-        (Some(device), None) => IdentityTabletDeviceArgs::Device(device),
-        (None, None) => IdentityTabletDeviceArgs::Automatic,
-        (None, Some(_)) => panic!("Device identifier must be provided when subdevice is given"),
     };
-    let device_opt = identify_tablet_device(identify_args);
 
-    match device_opt {
-        None => panic!("Could not identify tablet device with provided arguments"),
-        Some((mut server_device, device)) => {
-            let device_path = device.path.clone();
+    ADBServer::default().pair(address, args.code)?;
+
+    println!("Paired with {}", address);
+    println!("Use `forward --discover` to connect to it automatically from now on.");
+
+    Ok(())
+}
+
+fn control_client(args: ControlClientArgs) -> Result<(), Error> {
+    let mut stream = BufReader::new(TcpStream::connect(args.address)?);
+
+    while let Some(message) = read_message(&mut stream)? {
+        if let ControlMessage::Hello { version } = message {
+            if version != PROTOCOL_VERSION {
+                warn!(
+                    "Bridge speaks control protocol version {}, this client version {}",
+                    version, PROTOCOL_VERSION
+                );
+            }
+        }
+        println!("{:?}", message);
+    }
+
+    Ok(())
+}
+
+fn doctor() -> Result<(), Error> {
+    let checks = run_checks();
+
+    for check in checks.iter() {
+        println!("{}", check);
+    }
+
+    match checks
+        .iter()
+        .filter(|x| x.status == Status::Failure)
+        .count()
+    {
+        0 => Ok(()),
+        failures => Err(Error::ChecksFailed(failures)),
+    }
+}
+
+fn install_udev_rules(args: InstallUdevRulesArgs) -> Result<(), Error> {
+    fs::write(&args.output, udev_rule(&args.group)).map_err(|err| match err.kind() {
+        io::ErrorKind::PermissionDenied => Error::Permission {
+            path: args.output.clone(),
+            hint: "run the command with sudo",
+        },
+        _ => Error::Io(err),
+    })?;
+
+    println!("Wrote {}", args.output.display());
+    println!("To give your user access to {}:", UINPUT_PATH);
+    if !group_exists(&args.group) {
+        println!("  sudo groupadd --system {}", args.group);
+    }
+    println!("  sudo usermod -aG {} $USER", args.group);
+    println!("  echo uinput | sudo tee /etc/modules-load.d/uinput.conf");
+    println!("  sudo modprobe uinput");
+    println!(
+        "  sudo udevadm control --reload-rules && sudo udevadm trigger --sysname-match=uinput"
+    );
+    println!("Then log out and back in for the group membership to apply.");
+
+    Ok(())
+}
+
+fn inspect(args: InspectArgs) -> Result<(), Error> {
+    let identify_args = IdentityTabletDeviceArgs::new(&args.source, args.subdevice)?;
+    let (source, device) = identify_tablet_device(identify_args)?.ok_or_else(tablet_not_found)?;
+
+    let mut inspector = Inspector::new(&device);
+    let (sender, receiver) = mpsc::channel();
+    spawn_event_reader(
+        source,
+        device.path.clone(),
+        InputStream::Pen,
+        sender,
+        false,
+        None,
+        None,
+    );
+
+    signals::install_handlers().map_err(io::Error::from)?;
+
+    let refresh = Duration::from_millis(args.refresh_ms);
+    let mut last_render: Option<Instant> = None;
+    let mut stdout = io::stdout();
+
+    let screen = AlternateScreen::enter(&mut stdout)?;
 
-            let (uhandle, rotation_data_opt) = setup_virtual_input_device(
-                device,
-                String::from(args.name),
+    while !signals::stop_requested() {
+        match receiver.recv_timeout(POLL_INTERVAL.min(refresh)) {
+            Ok((_, Some(line), received_at)) => {
+                if let Ok((_, Some(event))) = parse_input_event(&line) {
+                    inspector.process(event, received_at);
+                }
+            }
+            Ok((_, None, _)) | Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+
+        if last_render.is_none_or(|x| x.elapsed() >= refresh) {
+            let now = Instant::now();
+            write!(stdout, "\x1b[H\x1b[2J{}", inspector.render(now))?;
+            stdout.flush()?;
+            last_render = Some(now);
+        }
+    }
+
+    drop(screen);
+
+    if !signals::stop_requested() {
+        warn!("Event stream from {} ended", device.path);
+    }
+
+    Ok(())
+}
+
+// Alternate screen without cursor like less or top, restored when dropped so errors do not leave
+// the terminal broken
+struct AlternateScreen;
+
+impl AlternateScreen {
+    fn enter(stdout: &mut io::Stdout) -> io::Result<Self> {
+        write!(stdout, "\x1b[?1049h\x1b[?25l")?;
+        Ok(AlternateScreen)
+    }
+}
+
+impl Drop for AlternateScreen {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = write!(stdout, "\x1b[?25h\x1b[?1049l");
+        let _ = stdout.flush();
+    }
+}
+
+fn forward(args: ForwardArgs) -> Result<(), Error> {
+    let forward_touch = args.forward_touch || args.kind.contains(&DeviceKind::Touchscreen);
+    let identify_args = IdentityTabletDeviceArgs::new(&args.source, args.subdevice)?;
+    let source_opt = match args.kind.contains(&DeviceKind::Pen) {
+        true => {
+            identify_tablet_device(identify_args)?.map(|(source, device)| (source, Some(device)))
+        }
+        false => identify_args
+            .sources
+            .into_iter()
+            .next()
+            .map(|source| (source, None)),
+    };
+
+    match source_opt {
+        None => Err(tablet_not_found()),
+        Some((source, mut device_opt)) => {
+            let subdevices = source.describe()?;
+
+            let touch_device_opt =
+                if args.gestures.is_empty() && !forward_touch {
+                    None
+                } else {
+                    match identify_touch_device(&subdevices, args.touch_subdevice) {
+                        None => return Err(Error::NotFound(String::from(
+                            "Could not identify touchscreen device, set it with --touch-subdevice",
+                        ))),
+                        Some(touch_device) => Some(touch_device),
+                    }
+                };
+
+            let mut passthrough_devices = vec![];
+            for kind in args
+                .kind
+                .iter()
+                .filter(|x| !matches!(x, DeviceKind::Pen | DeviceKind::Touchscreen))
+            {
+                let mut devices = subdevices
+                    .iter()
+                    .filter(|x| classify(x) == Some(*kind))
+                    .map(|x| (*kind, x.clone()))
+                    .peekable();
+                if devices.peek().is_none() {
+                    return Err(Error::NotFound(format!(
+                        "Could not identify a {:?} subdevice on {}, check `list-sub-device`",
+                        kind,
+                        source.name()
+                    )));
+                }
+                passthrough_devices.extend(devices);
+            }
+
+            let identity = match device_opt
+                .as_ref()
+                .or(touch_device_opt.as_ref())
+                .or(passthrough_devices.first().map(|(_, device)| device))
+            {
+                Some(device) => args.identity.resolve(args.name, device)?,
+                None => return Err(tablet_not_found()),
+            };
+
+            let forwarded = device_opt
+                .iter()
+                .chain(touch_device_opt.iter().filter(|_| forward_touch))
+                .chain(passthrough_devices.iter().map(|(_, device)| device));
+            for device in forwarded {
+                info!(
+                    "Forwarding {} ({}) from {}",
+                    device.name,
+                    device.path,
+                    source.name()
+                );
+            }
+
+            let touch_rotation = args.rotation.clone();
+
+            let metrics_opt = match args.metrics_listen {
+                None => None,
+                Some(address) => {
+                    let rotation_degrees = match args.rotation {
+                        None => 0,
+                        Some(Rotation::Rotation90) => 90,
+                        Some(Rotation::Rotation180) => 180,
+                        Some(Rotation::Rotation270) => 270,
+                    };
+                    let metrics = Arc::new(Metrics::new(rotation_degrees));
+                    serve_metrics(metrics.clone(), address)?;
+                    Some(metrics)
+                }
+            };
+
+            let control_opt = match args.control {
+                None => None,
+                Some(port) => {
+                    let state = ControlState {
+                        forwarding: false,
+                        monitor: args.monitor.clone(),
+                        profile: args.profile.clone(),
+                        zones: args.pad_zones.clone(),
+                    };
+                    let server =
+                        ControlServer::serve(SocketAddr::from((Ipv4Addr::LOCALHOST, port)), state)?;
+                    if let Err(err) = source.reverse_port(port) {
+                        warn!(
+                            "Could not set up the control channel on the device: {}",
+                            err
+                        );
+                    }
+                    Some(server)
+                }
+            };
+
+            let rotated = matches!(
                 args.rotation,
-                args.fallback_resolution,
-            )?;
-
-            let (event_writer_end, event_reader_end) = UnixStream::pair().unwrap();
-            std::thread::spawn(move || {
-                server_device
-                    .shell_command(["getevent", "-t", &device_path], event_writer_end)
-                    .unwrap();
-            });
+                Some(Rotation::Rotation90) | Some(Rotation::Rotation270)
+            );
+            let maximum = |axis| {
+                device_opt
+                    .iter()
+                    .flat_map(|device| device.events.absolute.iter())
+                    .find(|x| x.axis == axis)
+                    .map(|x| x.info.maximum)
+                    .unwrap_or(0)
+            };
+            let (maximum_x, maximum_y) = match rotated {
+                false => (maximum(AbsoluteAxis::X), maximum(AbsoluteAxis::Y)),
+                true => (maximum(AbsoluteAxis::Y), maximum(AbsoluteAxis::X)),
+            };
 
-            let event_reader = BufReader::new(event_reader_end);
+            let alignment_opt = args
+                .sidecar
+                .spawn(source.as_ref(), rotated)?
+                .map(|layout| InputAlignment::new(layout, maximum_x, maximum_y));
+
+            let mut eraser_opt = match (&args.eraser, device_opt.as_mut()) {
+                (Some(trigger), Some(device)) => {
+                    if !device.events.keys.contains(&Key::ButtonToolRubber) {
+                        device.events.keys.push(Key::ButtonToolRubber);
+                    }
+                    Some(
+                        EraserSwitch::new(trigger, &args.pad_zones, maximum_x, maximum_y)
+                            .map_err(Error::InvalidArguments)?,
+                    )
+                }
+                _ => None,
+            };
+
+            let mut tilt_opt = None;
+            if let Some(ref mut device) = device_opt {
+                tilt_opt = args.tilt.converter(device, rotated)?;
+                if let Some(ref tilt) = tilt_opt {
+                    tilt.configure(device);
+                }
+            }
+
+            let dry_run_opt = args
+                .dry_run
+                .as_deref()
+                .map(open_dry_run_output)
+                .transpose()?;
+
+            let mut pen_opt = None;
+            if let Some(device) = device_opt {
+                let device_path = device.path.clone();
+                let uhandle = open_output_device(&dry_run_opt)?;
+                let rotation_data_opt = setup_virtual_input_device(
+                    &uhandle,
+                    device,
+                    &identity,
+                    args.rotation.clone(),
+                    args.fallback_resolution,
+                    args.mode.clone(),
+                )
+                .map_err(Error::Uinput)?;
+                pen_opt = Some((device_path, uhandle, rotation_data_opt));
+            }
+
+            let mut relative_opt = match args.mode {
+                Mode::Absolute => None,
+                Mode::Relative => Some(RelativeMotion::new(
+                    args.relative_speed,
+                    args.relative_acceleration,
+                )),
+            };
+
+            let (sender, receiver) = mpsc::channel();
+
+            let mut pen_tracker = PenTracker::new();
+            let mut palm_rejection =
+                PalmRejection::new(args.palm_grace_ms, args.palm_max_touch_major);
+            let mut gesture_opt = None;
+            let mut touch_opt = None;
+
+            if let Some(touch_device) = touch_device_opt {
+                let touch_path = touch_device.path.clone();
+
+                if !args.gestures.is_empty() {
+                    let gesture_uhandle = open_output_device(&dry_run_opt)?;
+                    setup_gesture_input_device(
+                        &gesture_uhandle,
+                        &args.gestures,
+                        format!("{} Gestures", identity.name),
+                    )
+                    .map_err(Error::Uinput)?;
+                    let recognizer = GestureRecognizer::new(&touch_device, touch_rotation.clone());
+                    gesture_opt = Some((gesture_uhandle, recognizer));
+                }
+
+                if forward_touch {
+                    let touch_uhandle = open_output_device(&dry_run_opt)?;
+                    let touch_rotation_data_opt = setup_virtual_input_device(
+                        &touch_uhandle,
+                        touch_device,
+                        &identity.with_suffix("Touch"),
+                        touch_rotation,
+                        args.fallback_resolution,
+                        Mode::Absolute,
+                    )
+                    .map_err(Error::Uinput)?;
+                    touch_opt = Some((touch_uhandle, touch_rotation_data_opt));
+                }
+
+                spawn_event_reader(
+                    source.clone(),
+                    touch_path,
+                    InputStream::Touch,
+                    sender.clone(),
+                    args.reconnect,
+                    metrics_opt.clone(),
+                    control_opt.clone().filter(|_| pen_opt.is_none()),
+                );
+            }
+
+            let mut passthrough = vec![];
+            for (index, (kind, device)) in passthrough_devices.into_iter().enumerate() {
+                let device_path = device.path.clone();
+                let passthrough_uhandle = open_output_device(&dry_run_opt)?;
+                setup_virtual_input_device(
+                    &passthrough_uhandle,
+                    device,
+                    &identity.with_suffix(&format!("{:?}", kind)),
+                    None,
+                    args.fallback_resolution,
+                    Mode::Absolute,
+                )
+                .map_err(Error::Uinput)?;
+                passthrough.push(passthrough_uhandle);
+
+                spawn_event_reader(
+                    source.clone(),
+                    device_path,
+                    InputStream::Passthrough(index),
+                    sender.clone(),
+                    args.reconnect,
+                    metrics_opt.clone(),
+                    None,
+                );
+            }
+
+            signals::install_handlers().map_err(io::Error::from)?;
 
-            for line in event_reader.lines() {
-                if let Ok(ref line) = line {
-                    if let Some(event) = translate_device_event(&rotation_data_opt, &line) {
-                        uhandle.write(&[event.into_event().into_raw()]).unwrap();
+            let epoch = Instant::now();
+            let mut stats = Stats::new(epoch, estimate_clock_offset(source.as_ref(), epoch));
+            let stats_interval = args.stats_interval.map(Duration::from_secs);
+            let mut last_stats = Instant::now();
+
+            if let Some((ref device_path, _, _)) = pen_opt {
+                spawn_event_reader(
+                    source.clone(),
+                    device_path.clone(),
+                    InputStream::Pen,
+                    sender.clone(),
+                    args.reconnect,
+                    metrics_opt.clone(),
+                    control_opt.clone(),
+                );
+            }
+            drop(sender);
+
+            loop {
+                if signals::take_report_request()
+                    || stats_interval.is_some_and(|interval| last_stats.elapsed() >= interval)
+                {
+                    info!("Stats: {}", stats.report());
+                    last_stats = Instant::now();
+                }
+
+                if signals::stop_requested() {
+                    break;
+                }
+
+                // Runs after receive timeouts as well, held fingers usually send no frames
+                if let Some((gesture_uhandle, recognizer)) = gesture_opt.as_mut() {
+                    let (time, gestures) = recognizer.tick(Instant::now());
+                    perform_gestures(gesture_uhandle, &args.gestures, gestures, time);
+                }
+
+                let (stream, line, received_at) = match receiver.recv_timeout(POLL_INTERVAL) {
+                    Ok((stream, Some(line), received_at)) => (stream, line, received_at),
+                    Ok((InputStream::Pen, None, _)) => {
+                        if let Some((_, ref uhandle, _)) = pen_opt {
+                            let events = pen_tracker.release();
+                            // Touch is no longer suppressed once the released pen left range
+                            if let Some(event) = events.last() {
+                                palm_rejection.set_pen_in_range(
+                                    pen_tracker.state().in_range(),
+                                    event.as_event().time,
+                                );
+                            }
+                            if let Some(ref metrics) = metrics_opt {
+                                metrics.set_pen_phase(pen_tracker.state().phase());
+                            }
+                            write_pen_events(uhandle, &mut relative_opt, events, &metrics_opt)?;
+                        }
+                        continue;
+                    }
+                    Ok((_, None, _)) => continue,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+
+                let event = match parse_input_event(&line) {
+                    Ok((_, Some(event))) => event,
+                    Ok((_, None)) => continue,
+                    Err(err) => {
+                        stats.record_parse_failure();
+                        if let Some(ref metrics) = metrics_opt {
+                            metrics.record_parse_error();
+                        }
+                        warn!("Could not parse input event {:?}: {}", line, err);
+                        continue;
+                    }
+                };
+
+                let latency_opt = stats.record_event(&event, received_at);
+                if let (Some(metrics), Some(latency)) = (&metrics_opt, latency_opt) {
+                    metrics.record_latency(latency);
+                }
+
+                match (stream, &pen_opt) {
+                    (InputStream::Pen, Some((_, uhandle, rotation_data_opt))) => {
+                        let event = rotate_event(rotation_data_opt, event);
+                        let events = match tilt_opt.as_mut() {
+                            None => vec![event],
+                            Some(tilt) => tilt.process(event),
+                        };
+                        let events = events
+                            .into_iter()
+                            .flat_map(|event| match eraser_opt.as_mut() {
+                                None => vec![event],
+                                Some(eraser) => eraser.process(event),
+                            })
+                            .map(|event| match alignment_opt {
+                                None => event,
+                                Some(ref alignment) => alignment.align(event),
+                            })
+                            .flat_map(|event| pen_tracker.process(event))
+                            .collect();
+                        palm_rejection.set_pen_in_range(
+                            pen_tracker.state().in_range(),
+                            event.as_event().time,
+                        );
+                        if let Some(ref metrics) = metrics_opt {
+                            metrics.set_pen_phase(pen_tracker.state().phase());
+                        }
+                        write_pen_events(uhandle, &mut relative_opt, events, &metrics_opt)?;
+                    }
+                    (InputStream::Pen, None) => {}
+                    (InputStream::Touch, _) => {
+                        for event in palm_rejection.process(event) {
+                            if let Some((gesture_uhandle, recognizer)) = gesture_opt.as_mut() {
+                                perform_gestures(
+                                    gesture_uhandle,
+                                    &args.gestures,
+                                    recognizer.process(&event, received_at),
+                                    event.as_event().time,
+                                );
+                            }
+
+                            if let Some((ref touch_uhandle, ref touch_rotation_data_opt)) =
+                                touch_opt
+                            {
+                                let event = rotate_event(touch_rotation_data_opt, event);
+                                write_event(touch_uhandle, event, &metrics_opt)?;
+                            }
+                        }
+                    }
+                    (InputStream::Passthrough(index), _) => {
+                        write_event(&passthrough[index], event, &metrics_opt)?;
                     }
                 }
             }
 
-            uhandle.dev_destroy().unwrap();
+            info!("Stats: {}", stats.report());
+
+            if let Some((_, uhandle, _)) = pen_opt {
+                let events = pen_tracker.release();
+                write_pen_events(&uhandle, &mut relative_opt, events, &metrics_opt)?;
+                uhandle.dev_destroy().map_err(Error::Uinput)?;
+            }
+
+            for passthrough_uhandle in passthrough {
+                passthrough_uhandle.dev_destroy().map_err(Error::Uinput)?;
+            }
+
+            if let Some((gesture_uhandle, _)) = gesture_opt {
+                gesture_uhandle.dev_destroy().map_err(Error::Uinput)?;
+            }
+
+            if let Some((touch_uhandle, _)) = touch_opt {
+                touch_uhandle.dev_destroy().map_err(Error::Uinput)?;
+            }
 
             Ok(())
         }
     }
 }
 
-fn translate_device_event(
-    rotation_data_opt: &Option<RotationData>,
-    raw_event: &str,
-) -> Option<Event> {
-    match parse_input_event(&raw_event) {
-        Ok((_, event_opt)) => match event_opt {
-            None => None,
-            Some(event) => match event {
-                Event::Absolute(mut absolute_event) => {
-                    match rotation_data_opt {
-                        None => {}
-                        Some(rotation_data) => match rotation_data.rotation {
-                            Rotation::Rotation90 => match absolute_event.axis {
-                                AbsoluteAxis::X => {
-                                    absolute_event.axis = AbsoluteAxis::Y;
-                                    absolute_event.value = absolute_event.value;
-                                }
-                                AbsoluteAxis::Y => {
-                                    absolute_event.axis = AbsoluteAxis::X;
-                                    absolute_event.value =
-                                        rotation_data.maximum_y - absolute_event.value;
-                                }
-                                AbsoluteAxis::TiltX => {
-                                    absolute_event.axis = AbsoluteAxis::TiltY;
-                                    absolute_event.value = absolute_event.value;
-                                }
-                                AbsoluteAxis::TiltY => {
-                                    absolute_event.axis = AbsoluteAxis::TiltX;
-                                    absolute_event.value = -absolute_event.value;
-                                }
-                                _ => {}
-                            },
-                            Rotation::Rotation180 => match absolute_event.axis {
-                                AbsoluteAxis::X => {
-                                    absolute_event.value =
-                                        rotation_data.maximum_x - absolute_event.value;
-                                }
-                                AbsoluteAxis::Y => {
-                                    absolute_event.value =
-                                        rotation_data.maximum_y - absolute_event.value;
-                                }
-                                AbsoluteAxis::TiltX => {
-                                    absolute_event.value = -absolute_event.value;
-                                }
-                                AbsoluteAxis::TiltY => {
-                                    absolute_event.value = -absolute_event.value;
-                                }
-                                _ => {}
-                            },
-                            Rotation::Rotation270 => match absolute_event.axis {
-                                AbsoluteAxis::X => {
-                                    absolute_event.axis = AbsoluteAxis::Y;
-                                    absolute_event.value =
-                                        rotation_data.maximum_x - absolute_event.value;
-                                }
-                                AbsoluteAxis::Y => {
-                                    absolute_event.axis = AbsoluteAxis::X;
-                                    absolute_event.value = absolute_event.value;
-                                }
-                                AbsoluteAxis::TiltX => {
-                                    absolute_event.axis = AbsoluteAxis::TiltY;
-                                    absolute_event.value = -absolute_event.value;
-                                }
-                                AbsoluteAxis::TiltY => {
-                                    absolute_event.axis = AbsoluteAxis::TiltX;
-                                    absolute_event.value = absolute_event.value;
-                                }
-                                _ => {}
-                            },
-                        },
-                    }
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum InputStream {
+    Pen,
+    Touch,
+    Passthrough(usize),
+}
 
-                    Some(Event::Absolute(absolute_event))
+fn spawn_event_reader(
+    source: SharedSource,
+    device_path: String,
+    stream: InputStream,
+    sender: Sender<(InputStream, Option<String>, Instant)>,
+    reconnect: bool,
+    metrics_opt: Option<Arc<Metrics>>,
+    control_opt: Option<Arc<ControlServer>>,
+) {
+    std::thread::spawn(move || loop {
+        let (event_writer_end, event_reader_end) = match UnixStream::pair() {
+            Ok(pair) => pair,
+            Err(err) => {
+                error!("Could not create event stream socket: {}", err);
+                break;
+            }
+        };
+        let line_sender = sender.clone();
+
+        let line_reader = std::thread::spawn(move || {
+            let event_reader = BufReader::new(event_reader_end);
+
+            for line in event_reader.lines().map_while(Result::ok) {
+                if line_sender
+                    .send((stream, Some(line), Instant::now()))
+                    .is_err()
+                {
+                    break;
                 }
-                _ => Some(event),
-            },
-        },
-        Err(err) => {
-            eprintln!("Got error while parsing input event: {}", err);
-            None
+            }
+        });
+
+        if let Some(ref metrics) = metrics_opt {
+            metrics.set_connected(true);
+        }
+
+        if let Some(ref control) = control_opt {
+            control.set_forwarding(true);
+        }
+
+        let mut event_writer_end = event_writer_end;
+        let result = source.stream_events(&device_path, &mut event_writer_end);
+        drop(event_writer_end);
+
+        if let Some(ref metrics) = metrics_opt {
+            metrics.set_connected(false);
+        }
+
+        if let Some(ref control) = control_opt {
+            control.set_forwarding(false);
+        }
+
+        if let Err(err) = result {
+            warn!("Event stream from {} failed: {}", device_path, err);
+        }
+
+        let _ = line_reader.join();
+
+        if sender.send((stream, None, Instant::now())).is_err() {
+            break;
+        }
+
+        if !reconnect || signals::stop_requested() {
+            break;
+        }
+
+        std::thread::sleep(RECONNECT_DELAY);
+        info!("Reconnecting to {}", device_path);
+
+        if let Some(ref metrics) = metrics_opt {
+            metrics.record_reconnect();
+        }
+    });
+}
+
+fn perform_gestures(
+    uhandle: &OutputDevice,
+    bindings: &[GestureBinding],
+    gestures: Vec<Gesture>,
+    time: EventTime,
+) {
+    for gesture in gestures {
+        for binding in bindings.iter().filter(|x| x.gesture == gesture) {
+            if let Err(err) = perform_gesture_action(uhandle, &binding.action, time) {
+                warn!("Could not perform {:?} action: {}", gesture, err);
+            }
         }
     }
 }
 
+fn write_pen_events(
+    uhandle: &OutputDevice,
+    relative_opt: &mut Option<RelativeMotion>,
+    events: Vec<Event>,
+    metrics_opt: &Option<Arc<Metrics>>,
+) -> Result<(), Error> {
+    for event in events {
+        match relative_opt.as_mut() {
+            None => write_event(uhandle, event, metrics_opt)?,
+            Some(relative) => {
+                for event in relative.process(event) {
+                    write_event(uhandle, event, metrics_opt)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_event(
+    uhandle: &OutputDevice,
+    event: Event,
+    metrics_opt: &Option<Arc<Metrics>>,
+) -> Result<(), Error> {
+    uhandle
+        .write(&[event.into_event().into_raw()])
+        .map_err(Error::Uinput)?;
+
+    if let Some(metrics) = metrics_opt {
+        metrics.record_forwarded(&event);
+    }
+
+    Ok(())
+}
+
+fn rotate_event(rotation_data_opt: &Option<RotationData>, event: Event) -> Event {
+    match event {
+        Event::Absolute(mut absolute_event) => {
+            match rotation_data_opt {
+                None => {}
+                Some(rotation_data) => match rotation_data.rotation {
+                    Rotation::Rotation90 => match absolute_event.axis {
+                        AbsoluteAxis::X | AbsoluteAxis::MultitouchPositionX => {
+                            absolute_event.axis = swap_axis(absolute_event.axis);
+                        }
+                        AbsoluteAxis::Y | AbsoluteAxis::MultitouchPositionY => {
+                            absolute_event.axis = swap_axis(absolute_event.axis);
+                            absolute_event.value = rotation_data.maximum_y - absolute_event.value;
+                        }
+                        AbsoluteAxis::TiltX => {
+                            absolute_event.axis = AbsoluteAxis::TiltY;
+                        }
+                        AbsoluteAxis::TiltY => {
+                            absolute_event.axis = AbsoluteAxis::TiltX;
+                            absolute_event.value = -absolute_event.value;
+                        }
+                        _ => {}
+                    },
+                    Rotation::Rotation180 => match absolute_event.axis {
+                        AbsoluteAxis::X | AbsoluteAxis::MultitouchPositionX => {
+                            absolute_event.value = rotation_data.maximum_x - absolute_event.value;
+                        }
+                        AbsoluteAxis::Y | AbsoluteAxis::MultitouchPositionY => {
+                            absolute_event.value = rotation_data.maximum_y - absolute_event.value;
+                        }
+                        AbsoluteAxis::TiltX => {
+                            absolute_event.value = -absolute_event.value;
+                        }
+                        AbsoluteAxis::TiltY => {
+                            absolute_event.value = -absolute_event.value;
+                        }
+                        _ => {}
+                    },
+                    Rotation::Rotation270 => match absolute_event.axis {
+                        AbsoluteAxis::X | AbsoluteAxis::MultitouchPositionX => {
+                            absolute_event.axis = swap_axis(absolute_event.axis);
+                            absolute_event.value = rotation_data.maximum_x - absolute_event.value;
+                        }
+                        AbsoluteAxis::Y | AbsoluteAxis::MultitouchPositionY => {
+                            absolute_event.axis = swap_axis(absolute_event.axis);
+                        }
+                        AbsoluteAxis::TiltX => {
+                            absolute_event.axis = AbsoluteAxis::TiltY;
+                            absolute_event.value = -absolute_event.value;
+                        }
+                        AbsoluteAxis::TiltY => {
+                            absolute_event.axis = AbsoluteAxis::TiltX;
+                        }
+                        _ => {}
+                    },
+                },
+            }
+
+            Event::Absolute(absolute_event)
+        }
+        _ => event,
+    }
+}
+
+fn swap_axis(axis: AbsoluteAxis) -> AbsoluteAxis {
+    match axis {
+        AbsoluteAxis::X => AbsoluteAxis::Y,
+        AbsoluteAxis::Y => AbsoluteAxis::X,
+        AbsoluteAxis::MultitouchPositionX => AbsoluteAxis::MultitouchPositionY,
+        AbsoluteAxis::MultitouchPositionY => AbsoluteAxis::MultitouchPositionX,
+        _ => axis,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, ValueEnum)]
+enum Mode {
+    Absolute,
+    Relative,
+}
+
 #[derive(Clone, Debug, PartialEq, ValueEnum)]
-enum Rotation {
+pub enum Rotation {
     Rotation90,
     Rotation180,
     Rotation270,
@@ -275,91 +1270,104 @@ struct RotationData {
     maximum_y: i32,
 }
 
-enum IdentityTabletDeviceArgs {
-    Automatic,
-    Device(String),
-    DeviceAndSubdevice(String, String),
+struct IdentityTabletDeviceArgs {
+    sources: Vec<SharedSource>,
+    subdevice_identifier: Option<String>,
 }
 
-fn identify_tablet_device(args: IdentityTabletDeviceArgs) -> Option<(ADBServerDevice, ADBDevice)> {
-    let mut server = ADBServer::default();
-
-    let mut subdevice_identifier: Option<String> = None;
-
-    let identifiers: Vec<String> = match args {
-        IdentityTabletDeviceArgs::Automatic => server
-            .devices_long()
-            .unwrap()
-            .iter()
-            .map(|x| x.identifier.clone())
-            .collect(),
-        IdentityTabletDeviceArgs::Device(identifier) => vec![identifier],
-        IdentityTabletDeviceArgs::DeviceAndSubdevice(identifier, subident) => {
-            subdevice_identifier = Some(subident);
-            vec![identifier]
+impl IdentityTabletDeviceArgs {
+    fn new(source: &SourceArgs, subdevice: Option<String>) -> Result<Self, Error> {
+        if subdevice.is_some() && !source.is_explicit() {
+            return Err(Error::InvalidArguments(String::from(
+                "Device identifier must be provided when subdevice is given",
+            )));
         }
-    };
 
-    let mut found_device = None;
-
-    'device_loop: for identifier in identifiers {
-        let mut server_device = server
-            .get_device_by_name(&identifier)
-            .expect("Could not get device");
-
-        let (write_end, read_end) = UnixStream::pair().unwrap();
-
-        server_device
-            .shell_command(["getevent", "-p"], write_end)
-            .unwrap();
+        Ok(IdentityTabletDeviceArgs {
+            sources: source.sources()?,
+            subdevice_identifier: subdevice,
+        })
+    }
+}
 
-        let mut reader = BufReader::new(read_end);
-        let mut response = String::new();
-        reader.read_to_string(&mut response).expect("toc");
+fn tablet_not_found() -> Error {
+    Error::NotFound(String::from(
+        "Could not identify tablet device with provided arguments, check `list-device` and `list-sub-device`",
+    ))
+}
 
-        let (_, subdevices) = parse_devices(&response).expect("Could not parse device info");
+fn identify_tablet_device(
+    args: IdentityTabletDeviceArgs,
+) -> Result<Option<(SharedSource, ADBDevice)>, Error> {
+    for source in args.sources {
+        let subdevices = source.describe()?;
 
         for device in subdevices {
-            if (Some(device.name.to_owned()) == subdevice_identifier)
-                || subdevice_identifier.is_none()
-            {
-                if device.events.keys.contains(&Key::ButtonToolPen) {
-                    found_device = Some((server_device, device));
-                    break 'device_loop;
-                }
+            let selected = match args.subdevice_identifier {
+                Some(ref name) => &device.name == name,
+                None => classify(&device) == Some(DeviceKind::Pen),
+            };
+            if selected {
+                return Ok(Some((source, device)));
             }
         }
     }
 
-    found_device
+    Ok(None)
+}
+
+fn identify_touch_device(
+    subdevices: &[ADBDevice],
+    subdevice_identifier: Option<String>,
+) -> Option<ADBDevice> {
+    subdevices
+        .iter()
+        .find(|device| match subdevice_identifier {
+            None => classify(device) == Some(DeviceKind::Touchscreen),
+            Some(ref name) => &device.name == name,
+        })
+        .cloned()
 }
 
 fn setup_virtual_input_device(
+    uhandle: &OutputDevice,
     device: ADBDevice,
-    virtual_name: String,
+    identity: &DeviceIdentity,
     rotation_opt: Option<Rotation>,
     fallback_resolution: i32,
-) -> Result<(UInputHandle<File>, Option<RotationData>), io::Error> {
-    let uinput_file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .custom_flags(O_NONBLOCK)
-        .open("/dev/uinput")
-        .unwrap();
-
-    let uhandle = UInputHandle::new(uinput_file);
+    mode: Mode,
+) -> Result<Option<RotationData>, io::Error> {
+    match mode {
+        Mode::Absolute => {
+            if !device.events.keys.is_empty() {
+                uhandle.set_evbit(EventKind::Key)?;
+                for key in device.events.keys {
+                    uhandle.set_keybit(key)?;
+                }
+            }
 
-    if !device.events.keys.is_empty() {
-        uhandle.set_evbit(EventKind::Key)?;
-        for key in device.events.keys {
-            uhandle.set_keybit(key)?;
+            for property in device.properties {
+                uhandle.set_propbit(property)?;
+            }
+        }
+        Mode::Relative => {
+            uhandle.set_evbit(EventKind::Key)?;
+            uhandle.set_keybit(Key::ButtonLeft)?;
+            uhandle.set_keybit(Key::ButtonRight)?;
+            uhandle.set_keybit(Key::ButtonMiddle)?;
+            uhandle.set_evbit(EventKind::Relative)?;
+            uhandle.set_relbit(RelativeAxis::X)?;
+            uhandle.set_relbit(RelativeAxis::Y)?;
+            uhandle.set_propbit(InputProperty::Pointer)?;
         }
     }
 
     let mut rotation_data_opt: Option<RotationData> = None;
 
     if !device.events.absolute.is_empty() {
-        uhandle.set_evbit(EventKind::Absolute)?;
+        if mode == Mode::Absolute {
+            uhandle.set_evbit(EventKind::Absolute)?;
+        }
 
         let mut maximum_x_opt: Option<i32> = None;
         let mut maximum_y_opt: Option<i32> = None;
@@ -370,21 +1378,29 @@ fn setup_virtual_input_device(
             }
 
             if let Some(ref rotation) = rotation_opt {
-                if abs_setup.axis == AbsoluteAxis::X {
-                    maximum_x_opt = Some(abs_setup.info.maximum);
-                    if *rotation == Rotation::Rotation90 || *rotation == Rotation::Rotation270 {
-                        abs_setup.axis = AbsoluteAxis::Y;
+                let swap = *rotation == Rotation::Rotation90 || *rotation == Rotation::Rotation270;
+
+                match abs_setup.axis {
+                    AbsoluteAxis::X | AbsoluteAxis::MultitouchPositionX => {
+                        maximum_x_opt = Some(abs_setup.info.maximum);
+                        if swap {
+                            abs_setup.axis = swap_axis(abs_setup.axis);
+                        }
                     }
-                } else if abs_setup.axis == AbsoluteAxis::Y {
-                    maximum_y_opt = Some(abs_setup.info.maximum);
-                    if *rotation == Rotation::Rotation90 || *rotation == Rotation::Rotation270 {
-                        abs_setup.axis = AbsoluteAxis::X;
+                    AbsoluteAxis::Y | AbsoluteAxis::MultitouchPositionY => {
+                        maximum_y_opt = Some(abs_setup.info.maximum);
+                        if swap {
+                            abs_setup.axis = swap_axis(abs_setup.axis);
+                        }
                     }
+                    _ => {}
                 }
             }
 
-            uhandle.abs_setup(&uinput_abs_setup::from(abs_setup))?;
-            uhandle.set_absbit(abs_setup.axis)?;
+            if mode == Mode::Absolute {
+                uhandle.abs_setup(&uinput_abs_setup::from(abs_setup))?;
+                uhandle.set_absbit(abs_setup.axis)?;
+            }
         }
 
         if let (Some(rotation), Some(maximum_x), Some(maximum_y)) =
@@ -398,14 +1414,59 @@ fn setup_virtual_input_device(
         }
     }
 
+    uhandle.create(&identity.input_id(), identity.name.as_bytes())?;
+
+    Ok(rotation_data_opt)
+}
+
+fn setup_gesture_input_device(
+    uhandle: &OutputDevice,
+    bindings: &[GestureBinding],
+    virtual_name: String,
+) -> Result<(), io::Error> {
+    uhandle.set_evbit(EventKind::Key)?;
+    uhandle.set_evbit(EventKind::Relative)?;
+    uhandle.set_relbit(RelativeAxis::Wheel)?;
+    uhandle.set_relbit(RelativeAxis::HorizontalWheel)?;
+
+    for binding in bindings {
+        if let GestureAction::Keys(ref keys) = binding.action {
+            for key in keys {
+                uhandle.set_keybit(*key)?;
+            }
+        }
+    }
+
     let input_id = InputId {
-        bustype: input_linux::sys::BUS_USB,
+        bustype: input_linux::sys::BUS_VIRTUAL,
         vendor: 0x1234,
-        product: 0x5678,
+        product: 0x5679,
         version: 0,
     };
 
-    uhandle.create(&input_id, virtual_name.as_bytes(), 0, &[])?;
+    uhandle.create(&input_id, virtual_name.as_bytes())
+}
+
+fn open_output_device(dry_run_opt: &Option<SharedWriter>) -> Result<OutputDevice, Error> {
+    if let Some(output) = dry_run_opt {
+        return Ok(OutputDevice::dry_run(output.clone()));
+    }
+
+    let uinput_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(O_NONBLOCK)
+        .open(UINPUT_PATH)
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::PermissionDenied => Error::Permission {
+                path: PathBuf::from(UINPUT_PATH),
+                hint: UINPUT_PERMISSION_HINT,
+            },
+            io::ErrorKind::NotFound => Error::UinputMissing {
+                path: PathBuf::from(UINPUT_PATH),
+            },
+            _ => Error::Uinput(err),
+        })?;
 
-    Ok((uhandle, rotation_data_opt))
+    Ok(OutputDevice::Uinput(UInputHandle::new(uinput_file)))
 }
@@ -0,0 +1,238 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::PathBuf,
+    process::{Child, ChildStdout, Command, Stdio},
+};
+
+use clap::ValueEnum;
+use input_linux::{Event, EventKind, EventTime, InputEvent};
+
+use crate::{
+    companion::format_getevent_line,
+    error::Error,
+    evtest::parse_evtest_event,
+    source::{EventSource, DESCRIBE_COMMAND},
+};
+
+const RAW_EVENT_SIZE: usize = 24;
+
+#[derive(Clone, Debug, PartialEq, ValueEnum)]
+pub enum StreamFormat {
+    Getevent,
+    // struct input_event as read from /dev/input/eventN on a 64-bit little endian device
+    Raw,
+    // evtest on embedded Linux tablets without getevent
+    Evtest,
+}
+
+// Runs getevent, evtest, or cat on the device node, through a user supplied command prefix like
+// `ssh tablet` for devices which are not reachable over ADB.
+pub struct CommandSource {
+    prefix: String,
+    stream_format: StreamFormat,
+    description: Option<PathBuf>,
+}
+
+impl CommandSource {
+    pub fn new(prefix: String, stream_format: StreamFormat, description: Option<PathBuf>) -> Self {
+        CommandSource {
+            prefix,
+            stream_format,
+            description,
+        }
+    }
+
+    fn spawn(&self, command: &[&str]) -> Result<(Child, ChildStdout), Error> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(format!(
+                "{} {}",
+                self.prefix,
+                command
+                    .iter()
+                    .map(|x| shell_quote(x))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| Error::Command(String::from("no output")))?;
+        Ok((child, stdout))
+    }
+
+    fn wait(&self, mut child: Child, result: Result<(), Error>) -> Result<(), Error> {
+        if result.is_err() {
+            let _ = child.kill();
+        }
+        let status = child.wait()?;
+        result?;
+
+        match status.success() {
+            true => Ok(()),
+            false => Err(Error::Command(format!("`{}` {}", self.prefix, status))),
+        }
+    }
+}
+
+impl EventSource for CommandSource {
+    fn name(&self) -> String {
+        format!("`{}`", self.prefix)
+    }
+
+    fn shell(&self, command: &[&str], output: &mut dyn Write) -> Result<(), Error> {
+        if let Some(ref description) = self.description {
+            if command == DESCRIBE_COMMAND {
+                io::copy(&mut File::open(description)?, output)?;
+                return Ok(());
+            }
+        }
+
+        let (child, mut stdout) = self.spawn(command)?;
+        let result = io::copy(&mut stdout, output)
+            .map(|_| ())
+            .map_err(Error::from);
+        self.wait(child, result)
+    }
+
+    fn stream_events(&self, device_path: &str, output: &mut dyn Write) -> Result<(), Error> {
+        match self.stream_format {
+            StreamFormat::Getevent => self.shell(&["getevent", "-t", device_path], output),
+            StreamFormat::Raw => {
+                let (child, mut stdout) = self.spawn(&["cat", device_path])?;
+                let result = decode_raw_events(&mut stdout, output);
+                self.wait(child, result)
+            }
+            StreamFormat::Evtest => {
+                let (child, stdout) = self.spawn(&["evtest", device_path])?;
+                let result = convert_evtest_events(&mut BufReader::new(stdout), output);
+                self.wait(child, result)
+            }
+        }
+    }
+}
+
+// evtest prints the device description before the events, those lines do not parse
+pub fn convert_evtest_events(input: &mut dyn BufRead, output: &mut dyn Write) -> Result<(), Error> {
+    for line in input.lines() {
+        if let Ok((_, Some(event))) = parse_evtest_event(&line?) {
+            writeln!(output, "{}", format_getevent_line(&event))?;
+        }
+    }
+
+    Ok(())
+}
+
+// The prefix is a shell command line while the arguments are passed as is
+fn shell_quote(argument: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "/-_.,:=@%+".contains(c);
+    match !argument.is_empty() && argument.chars().all(safe) {
+        true => argument.to_owned(),
+        false => format!("'{}'", argument.replace('\'', "'\\''")),
+    }
+}
+
+pub fn decode_raw_events(input: &mut dyn Read, output: &mut dyn Write) -> Result<(), Error> {
+    let mut buffer = [0u8; RAW_EVENT_SIZE];
+
+    loop {
+        match input.read_exact(&mut buffer) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err.into()),
+        }
+
+        let field = |start: usize, size: usize| {
+            let mut bytes = [0u8; 8];
+            bytes[..size].copy_from_slice(&buffer[start..start + size]);
+            u64::from_le_bytes(bytes)
+        };
+
+        // Event kinds and codes input-linux does not know are dropped like unparseable lines
+        let Ok(kind) = EventKind::from_type(field(16, 2) as u16) else {
+            continue;
+        };
+        let raw = InputEvent {
+            time: EventTime::new(field(0, 8) as i64, field(8, 8) as i64),
+            kind,
+            code: field(18, 2) as u16,
+            value: field(20, 4) as u32 as i32,
+        };
+        if let Ok(event) = Event::new(raw) {
+            writeln!(output, "{}", format_getevent_line(&event))?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn raw_event(seconds: u64, microseconds: u64, kind: u16, code: u16, value: i32) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&seconds.to_le_bytes());
+        bytes.extend_from_slice(&microseconds.to_le_bytes());
+        bytes.extend_from_slice(&kind.to_le_bytes());
+        bytes.extend_from_slice(&code.to_le_bytes());
+        bytes.extend_from_slice(&value.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn it_streams_through_a_command() {
+        let mut input = raw_event(1338055, 981824, 3, 0x1a, -29);
+        input.extend(raw_event(1338055, 981824, 0, 0, 0));
+        input.extend_from_slice(&[1, 2, 3]);
+
+        let mut output = vec![];
+        decode_raw_events(&mut input.as_slice(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "[ 1338055.981824] 0003 001a ffffffe3\n\
+             [ 1338055.981824] 0000 0000 00000000\n"
+        );
+
+        let mut output = vec![];
+        convert_evtest_events(
+            &mut include_str!("../events-evtest.txt").as_bytes(),
+            &mut output,
+        )
+        .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output.lines().next(),
+            Some("[1700000000.120003] 0001 0140 00000001")
+        );
+
+        // The prefix is a shell command, echo stands for ssh and friends
+        let source = CommandSource::new(String::from("echo"), StreamFormat::Getevent, None);
+        let mut output = vec![];
+        source
+            .stream_events("/dev/input/event9", &mut output)
+            .unwrap();
+        assert_eq!(output, b"getevent -t /dev/input/event9\n");
+
+        let source =
+            CommandSource::new(String::from("printf '%s\\n'"), StreamFormat::Getevent, None);
+        let mut output = vec![];
+        source
+            .stream_events("/dev/input/by-id/it's a pen", &mut output)
+            .unwrap();
+        assert_eq!(output, b"getevent\n-t\n/dev/input/by-id/it's a pen\n");
+
+        let source = CommandSource::new(
+            String::from("false"),
+            StreamFormat::Raw,
+            Some(PathBuf::from("devices.txt")),
+        );
+        assert_eq!(source.describe().unwrap()[4].name, "sec_e-pen");
+        assert!(source
+            .stream_events("/dev/input/event9", &mut vec![])
+            .is_err());
+    }
+}
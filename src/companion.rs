@@ -0,0 +1,577 @@
+use std::{
+    io::{self, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::Mutex,
+};
+
+use adb_client::{ADBDeviceExt, ADBServerDevice};
+use input_linux::{
+    AbsoluteAxis, AbsoluteEvent, AbsoluteInfo, AbsoluteInfoSetup, Event, EventTime, Key, KeyEvent,
+    KeyState, SynchronizeEvent,
+};
+use log::info;
+
+use crate::{
+    error::Error,
+    parser::{ADBDevice, ADBDeviceEvents},
+    source::{reverse_port, EventSource},
+};
+
+// Protocol spoken by the companion app, all integers and floats are big endian:
+//   hello:  "ATB" version:u8 name_length:u16 name:utf8 width:u32 height:u32 xdpi:f32 ydpi:f32
+//   packet: kind:u8 followed by
+//     motion (1): action:u8 tool:u8 buttons:u8 sample_count:u16
+//                 sample_count * (time_us:i64 x:f32 y:f32 pressure:f32 tilt:f32 orientation:f32)
+//     ping (2):   nothing
+// Samples are ordered oldest first, the last one being the current MotionEvent position.
+pub const APP_PACKAGE: &str = "com.example.myapplication";
+pub const APP_ACTIVITY: &str = "MainActivity";
+
+const MAGIC: &[u8; 3] = b"ATB";
+const VERSION: u8 = 1;
+
+const PACKET_MOTION: u8 = 1;
+const PACKET_PING: u8 = 2;
+
+const ACTION_DOWN: u8 = 0;
+const ACTION_UP: u8 = 1;
+const ACTION_MOVE: u8 = 2;
+const ACTION_CANCEL: u8 = 3;
+const ACTION_HOVER_MOVE: u8 = 7;
+const ACTION_HOVER_ENTER: u8 = 9;
+const ACTION_HOVER_EXIT: u8 = 10;
+
+const TOOL_STYLUS: u8 = 2;
+const TOOL_ERASER: u8 = 4;
+
+const BUTTON_PRIMARY: u8 = 1;
+const BUTTON_SECONDARY: u8 = 2;
+
+const SUBPIXELS: f32 = 8.0;
+const PRESSURE_MAXIMUM: i32 = 4096;
+const TILT_MAXIMUM: i32 = 90;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hello {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub xdpi: f32,
+    pub ydpi: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub time_us: i64,
+    pub x: f32,
+    pub y: f32,
+    pub pressure: f32,
+    pub tilt: f32,
+    pub orientation: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Motion {
+    pub action: u8,
+    pub tool: u8,
+    pub buttons: u8,
+    pub samples: Vec<Sample>,
+}
+
+pub fn read_hello(reader: &mut impl Read) -> Result<Hello, Error> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+
+    if &magic[..3] != MAGIC || magic[3] != VERSION {
+        return Err(Error::Companion(format!(
+            "Unsupported companion app handshake {:?}",
+            magic
+        )));
+    }
+
+    let name_length = read_u16(reader)? as usize;
+    let mut name = vec![0u8; name_length];
+    reader.read_exact(&mut name)?;
+
+    Ok(Hello {
+        name: String::from_utf8_lossy(&name).into_owned(),
+        width: read_u32(reader)?,
+        height: read_u32(reader)?,
+        xdpi: read_f32(reader)?,
+        ydpi: read_f32(reader)?,
+    })
+}
+
+// Returns None once the app closes the connection.
+pub fn read_motion(reader: &mut impl Read) -> Result<Option<Motion>, Error> {
+    loop {
+        let mut kind = [0u8; 1];
+        match reader.read_exact(&mut kind) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+
+        match kind[0] {
+            PACKET_PING => continue,
+            PACKET_MOTION => {
+                let mut header = [0u8; 3];
+                reader.read_exact(&mut header)?;
+                let sample_count = read_u16(reader)?;
+
+                let mut samples = Vec::with_capacity(sample_count as usize);
+                for _ in 0..sample_count {
+                    samples.push(Sample {
+                        time_us: read_i64(reader)?,
+                        x: read_f32(reader)?,
+                        y: read_f32(reader)?,
+                        pressure: read_f32(reader)?,
+                        tilt: read_f32(reader)?,
+                        orientation: read_f32(reader)?,
+                    });
+                }
+
+                return Ok(Some(Motion {
+                    action: header[0],
+                    tool: header[1],
+                    buttons: header[2],
+                    samples,
+                }));
+            }
+            kind => {
+                return Err(Error::Companion(format!(
+                    "Unknown companion app packet {}",
+                    kind
+                )))
+            }
+        }
+    }
+}
+
+pub fn start_companion_app(identifier: &str, port: u16) -> Result<(), Error> {
+    reverse_port(identifier, port)?;
+    ADBServerDevice::new(identifier.to_string(), None).run_activity(APP_PACKAGE, APP_ACTIVITY)?;
+    Ok(())
+}
+
+fn read_u16(reader: &mut impl Read) -> io::Result<u16> {
+    let mut buffer = [0u8; 2];
+    reader.read_exact(&mut buffer)?;
+    Ok(u16::from_be_bytes(buffer))
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut buffer = [0u8; 4];
+    reader.read_exact(&mut buffer)?;
+    Ok(u32::from_be_bytes(buffer))
+}
+
+fn read_i64(reader: &mut impl Read) -> io::Result<i64> {
+    let mut buffer = [0u8; 8];
+    reader.read_exact(&mut buffer)?;
+    Ok(i64::from_be_bytes(buffer))
+}
+
+fn read_f32(reader: &mut impl Read) -> io::Result<f32> {
+    Ok(f32::from_bits(read_u32(reader)?))
+}
+
+pub fn device_description(hello: &Hello) -> ADBDevice {
+    let axis = |axis, minimum, maximum, resolution| AbsoluteInfoSetup {
+        axis,
+        info: AbsoluteInfo {
+            value: 0,
+            minimum,
+            maximum,
+            fuzz: 0,
+            flat: 0,
+            resolution,
+        },
+    };
+    let units_per_mm = |dpi: f32| (dpi / 25.4 * SUBPIXELS).round() as i32;
+
+    ADBDevice {
+        path: String::from("companion"),
+        name: hello.name.clone(),
+        id: None,
+        events: ADBDeviceEvents {
+            keys: vec![
+                Key::ButtonToolPen,
+                Key::ButtonToolRubber,
+                Key::ButtonTouch,
+                Key::ButtonStylus,
+                Key::ButtonStylus2,
+            ],
+            relative: vec![],
+            absolute: vec![
+                axis(
+                    AbsoluteAxis::X,
+                    0,
+                    (hello.width as f32 * SUBPIXELS) as i32,
+                    units_per_mm(hello.xdpi),
+                ),
+                axis(
+                    AbsoluteAxis::Y,
+                    0,
+                    (hello.height as f32 * SUBPIXELS) as i32,
+                    units_per_mm(hello.ydpi),
+                ),
+                axis(AbsoluteAxis::Pressure, 0, PRESSURE_MAXIMUM, 0),
+                axis(AbsoluteAxis::TiltX, -TILT_MAXIMUM, TILT_MAXIMUM, 0),
+                axis(AbsoluteAxis::TiltY, -TILT_MAXIMUM, TILT_MAXIMUM, 0),
+            ],
+            switches: vec![],
+        },
+        properties: vec![],
+    }
+}
+
+// Turns MotionEvents into evdev frames shaped like the ones of a getevent pen device.
+#[derive(Default)]
+pub struct MotionDecoder {
+    tool: Option<Key>,
+    touching: bool,
+    buttons: u8,
+    last_values: Vec<(AbsoluteAxis, i32)>,
+}
+
+impl MotionDecoder {
+    pub fn new() -> Self {
+        MotionDecoder::default()
+    }
+
+    pub fn decode(&mut self, motion: &Motion) -> Vec<Event> {
+        let mut output = vec![];
+
+        if motion.tool != TOOL_STYLUS && motion.tool != TOOL_ERASER {
+            return output;
+        }
+
+        let tool_key = if motion.tool == TOOL_ERASER {
+            Key::ButtonToolRubber
+        } else {
+            Key::ButtonToolPen
+        };
+
+        let (in_range, touching) = match motion.action {
+            ACTION_DOWN | ACTION_MOVE => (true, true),
+            ACTION_UP | ACTION_CANCEL | ACTION_HOVER_ENTER | ACTION_HOVER_MOVE => (true, false),
+            ACTION_HOVER_EXIT => (false, false),
+            _ => (self.tool.is_some(), self.touching),
+        };
+
+        for (index, sample) in motion.samples.iter().enumerate() {
+            let time = EventTime::new(
+                sample.time_us.div_euclid(1_000_000),
+                sample.time_us.rem_euclid(1_000_000),
+            );
+            let is_first = index == 0;
+            let is_current = index + 1 == motion.samples.len();
+
+            // Keys change before the axis values like getevent streams. Presses apply from the
+            // first sample so historical pressure follows BTN_TOUCH, releases to the current one.
+            if is_first && in_range && self.tool != Some(tool_key) {
+                if let Some(previous_tool) = self.tool.take() {
+                    output.push(key_event(time, previous_tool, false));
+                }
+                output.push(key_event(time, tool_key, true));
+                self.tool = Some(tool_key);
+            }
+
+            if touching != self.touching && (if touching { is_first } else { is_current }) {
+                output.push(key_event(time, Key::ButtonTouch, touching));
+                self.touching = touching;
+            }
+
+            if is_current {
+                for (mask, key) in [
+                    (BUTTON_PRIMARY, Key::ButtonStylus),
+                    (BUTTON_SECONDARY, Key::ButtonStylus2),
+                ] {
+                    let pressed = in_range && motion.buttons & mask != 0;
+                    if pressed != (self.buttons & mask != 0) {
+                        output.push(key_event(time, key, pressed));
+                        self.buttons ^= mask;
+                    }
+                }
+            }
+
+            let (tilt_x, tilt_y) = tilt_components(sample.tilt, sample.orientation);
+            let pressure = if touching || !is_current {
+                (sample.pressure.clamp(0.0, 1.0) * PRESSURE_MAXIMUM as f32).round() as i32
+            } else {
+                0
+            };
+
+            for (axis, value) in [
+                (AbsoluteAxis::X, (sample.x * SUBPIXELS).round() as i32),
+                (AbsoluteAxis::Y, (sample.y * SUBPIXELS).round() as i32),
+                (AbsoluteAxis::Pressure, pressure),
+                (AbsoluteAxis::TiltX, tilt_x),
+                (AbsoluteAxis::TiltY, tilt_y),
+            ] {
+                if self.update(axis, value) {
+                    output.push(Event::Absolute(AbsoluteEvent::new(time, axis, value)));
+                }
+            }
+
+            if is_current && !in_range {
+                if let Some(previous_tool) = self.tool.take() {
+                    output.push(key_event(time, previous_tool, false));
+                }
+            }
+
+            output.push(Event::Synchronize(SynchronizeEvent::report(time)));
+        }
+
+        output
+    }
+
+    fn update(&mut self, axis: AbsoluteAxis, value: i32) -> bool {
+        match self.last_values.iter_mut().find(|(x, _)| *x == axis) {
+            Some((_, last)) if *last == value => false,
+            Some((_, last)) => {
+                *last = value;
+                true
+            }
+            None => {
+                self.last_values.push((axis, value));
+                true
+            }
+        }
+    }
+}
+
+fn key_event(time: EventTime, key: Key, pressed: bool) -> Event {
+    Event::Key(KeyEvent::new(time, key, KeyState::pressed(pressed)))
+}
+
+// Android reports tilt as the angle from the screen normal and orientation as the direction
+// the stylus points to, evdev expects the tilt angle projected on each axis in degrees.
+fn tilt_components(tilt: f32, orientation: f32) -> (i32, i32) {
+    let tangent = tilt.clamp(0.0, 1.5).tan();
+    let tilt_x = (tangent * orientation.sin()).atan().to_degrees();
+    let tilt_y = (-tangent * orientation.cos()).atan().to_degrees();
+
+    (
+        (tilt_x.round() as i32).clamp(-TILT_MAXIMUM, TILT_MAXIMUM),
+        (tilt_y.round() as i32).clamp(-TILT_MAXIMUM, TILT_MAXIMUM),
+    )
+}
+
+pub fn format_getevent_line(event: &Event) -> String {
+    let raw = event.as_event();
+    format!(
+        "[{:>8}.{:06}] {:04x} {:04x} {:08x}",
+        raw.time.seconds(),
+        raw.time.microseconds(),
+        raw.kind as u16,
+        raw.code,
+        raw.value as u32
+    )
+}
+
+// Waits for the companion app on a socket, usually reached from the device through adb reverse.
+pub struct CompanionSource {
+    address: SocketAddr,
+    device: Option<String>,
+    listener: TcpListener,
+    pending: Mutex<Option<(BufReader<TcpStream>, Hello)>>,
+}
+
+impl CompanionSource {
+    pub fn bind(address: SocketAddr, device: Option<String>) -> Result<Self, Error> {
+        let listener = TcpListener::bind(address)?;
+
+        Ok(CompanionSource {
+            address: listener.local_addr()?,
+            device,
+            listener,
+            pending: Mutex::new(None),
+        })
+    }
+
+    fn take_connection(&self) -> Result<(BufReader<TcpStream>, Hello), Error> {
+        if let Some(connection) = self.pending.lock().ok().and_then(|mut x| x.take()) {
+            return Ok(connection);
+        }
+
+        info!("Waiting for the companion app on {}", self.address);
+        let (stream, peer) = self.listener.accept()?;
+        stream.set_nodelay(true)?;
+
+        let mut reader = BufReader::new(stream);
+        let hello = read_hello(&mut reader)?;
+        info!(
+            "Companion app connected from {} ({}, {}x{})",
+            peer, hello.name, hello.width, hello.height
+        );
+
+        Ok((reader, hello))
+    }
+}
+
+impl EventSource for CompanionSource {
+    fn name(&self) -> String {
+        format!("companion app on {}", self.address)
+    }
+
+    fn shell(&self, _command: &[&str], _output: &mut dyn Write) -> Result<(), Error> {
+        Err(Error::InvalidArguments(String::from(
+            "The companion app does not run shell commands",
+        )))
+    }
+
+    fn describe(&self) -> Result<Vec<ADBDevice>, Error> {
+        let (reader, hello) = self.take_connection()?;
+        let description = device_description(&hello);
+
+        if let Ok(mut pending) = self.pending.lock() {
+            *pending = Some((reader, hello));
+        }
+
+        Ok(vec![description])
+    }
+
+    fn reverse_port(&self, port: u16) -> Result<(), Error> {
+        match self.device {
+            Some(ref identifier) => reverse_port(identifier, port),
+            None => Err(Error::InvalidArguments(format!(
+                "No ADB device to reverse ports on, run `adb reverse tcp:{0} tcp:{0}`",
+                port
+            ))),
+        }
+    }
+
+    fn stream_events(&self, _device_path: &str, output: &mut dyn Write) -> Result<(), Error> {
+        let (mut reader, _) = self.take_connection()?;
+        let mut decoder = MotionDecoder::new();
+
+        while let Some(motion) = read_motion(&mut reader)? {
+            for event in decoder.decode(&motion) {
+                writeln!(output, "{}", format_getevent_line(&event))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_input_event;
+    use pretty_assertions::assert_eq;
+    use std::f32::consts::FRAC_PI_4;
+
+    fn write_hello(output: &mut Vec<u8>) {
+        output.extend_from_slice(b"ATB\x01");
+        output.extend_from_slice(&7u16.to_be_bytes());
+        output.extend_from_slice(b"Tab S10");
+        output.extend_from_slice(&2800u32.to_be_bytes());
+        output.extend_from_slice(&1752u32.to_be_bytes());
+        output.extend_from_slice(&254.0f32.to_be_bytes());
+        output.extend_from_slice(&254.0f32.to_be_bytes());
+    }
+
+    fn write_motion(output: &mut Vec<u8>, action: u8, buttons: u8, samples: &[(i64, f32, f32)]) {
+        output.extend_from_slice(&[PACKET_MOTION, action, TOOL_STYLUS, buttons]);
+        output.extend_from_slice(&(samples.len() as u16).to_be_bytes());
+        for (time_us, x, pressure) in samples {
+            output.extend_from_slice(&time_us.to_be_bytes());
+            output.extend_from_slice(&x.to_be_bytes());
+            output.extend_from_slice(&100.0f32.to_be_bytes());
+            output.extend_from_slice(&pressure.to_be_bytes());
+            output.extend_from_slice(&FRAC_PI_4.to_be_bytes());
+            output.extend_from_slice(&0.0f32.to_be_bytes());
+        }
+    }
+
+    fn time(us: i64) -> EventTime {
+        EventTime::new(us / 1_000_000, us % 1_000_000)
+    }
+
+    #[test]
+    fn it_decodes_companion_stream_into_getevent_frames() {
+        let mut stream = vec![];
+        write_hello(&mut stream);
+        write_motion(
+            &mut stream,
+            ACTION_HOVER_ENTER,
+            0,
+            &[(1_000_000, 10.0, 0.0)],
+        );
+        stream.push(PACKET_PING);
+        write_motion(
+            &mut stream,
+            ACTION_DOWN,
+            BUTTON_PRIMARY,
+            &[(1_010_000, 11.0, 0.5), (1_020_000, 12.0, 0.25)],
+        );
+        write_motion(&mut stream, ACTION_HOVER_EXIT, 0, &[(1_030_000, 12.0, 0.0)]);
+
+        let source = CompanionSource::bind("127.0.0.1:0".parse().unwrap(), None).unwrap();
+        let address = source.address;
+        let app = std::thread::spawn(move || {
+            TcpStream::connect(address)
+                .unwrap()
+                .write_all(&stream)
+                .unwrap();
+        });
+
+        let devices = source.describe().unwrap();
+        assert_eq!(devices[0].name, "Tab S10");
+        assert_eq!(devices[0].events.absolute[0].info.maximum, 22400);
+        assert_eq!(devices[0].events.absolute[0].info.resolution, 80);
+
+        let mut output = vec![];
+        source.stream_events("companion", &mut output).unwrap();
+        app.join().unwrap();
+
+        let events: Vec<Event> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .filter_map(|line| parse_input_event(line).unwrap().1)
+            .collect();
+
+        // The historical touch-down sample starts with BTN_TOUCH like live samples
+        let touch_down = events
+            .iter()
+            .position(|x| x.as_event().time == time(1_010_000))
+            .unwrap();
+        assert_eq!(
+            events[touch_down],
+            key_event(time(1_010_000), Key::ButtonTouch, true)
+        );
+
+        let events: Vec<Event> = events
+            .into_iter()
+            .filter(|event| !matches!(event, Event::Absolute(_)))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                key_event(time(1_000_000), Key::ButtonToolPen, true),
+                Event::Synchronize(SynchronizeEvent::report(time(1_000_000))),
+                key_event(time(1_010_000), Key::ButtonTouch, true),
+                Event::Synchronize(SynchronizeEvent::report(time(1_010_000))),
+                key_event(time(1_020_000), Key::ButtonStylus, true),
+                Event::Synchronize(SynchronizeEvent::report(time(1_020_000))),
+                key_event(time(1_030_000), Key::ButtonTouch, false),
+                key_event(time(1_030_000), Key::ButtonStylus, false),
+                key_event(time(1_030_000), Key::ButtonToolPen, false),
+                Event::Synchronize(SynchronizeEvent::report(time(1_030_000))),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_projects_tilt_on_axes() {
+        assert_eq!(tilt_components(0.0, 1.0), (0, 0));
+        assert_eq!(tilt_components(FRAC_PI_4, 0.0), (0, -45));
+        assert_eq!(
+            tilt_components(FRAC_PI_4, std::f32::consts::FRAC_PI_2),
+            (45, 0)
+        );
+    }
+}
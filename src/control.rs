@@ -0,0 +1,399 @@
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use log::{info, warn};
+
+use crate::error::Error;
+
+// Messages pushed by the bridge to the companion app, all integers and floats are big endian:
+//   frame:  kind:u8 length:u32 payload
+//   string: length:u16 utf8
+// The first frame is always a hello carrying the protocol version, clients skip frames of
+// unknown kinds using their length so new messages can be added without breaking older apps.
+pub const PROTOCOL_VERSION: u8 = 1;
+
+const MESSAGE_HELLO: u8 = 1;
+const MESSAGE_FORWARDING: u8 = 2;
+const MESSAGE_MONITOR: u8 = 3;
+const MESSAGE_PROFILE: u8 = 4;
+const MESSAGE_ZONES: u8 = 5;
+
+// Broadcasts happen on the event path, a client not reading its socket is dropped rather than
+// stalling forwarding
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+// Position and size are fractions of the tablet screen.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PadZone {
+    pub name: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl FromStr for PadZone {
+    type Err = String;
+
+    // <name>=<x>,<y>,<width>,<height>
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (name, rectangle) = input
+            .split_once('=')
+            .ok_or_else(|| format!("Expected <name>=<x>,<y>,<width>,<height>, got '{}'", input))?;
+
+        let values = rectangle
+            .split(',')
+            .map(|x| x.trim().parse::<f32>())
+            .collect::<Result<Vec<f32>, _>>()
+            .map_err(|err| format!("Invalid zone '{}': {}", input, err))?;
+
+        match values[..] {
+            [x, y, width, height]
+                if values.iter().all(|x| (0.0..=1.0).contains(x))
+                    && x + width <= 1.0
+                    && y + height <= 1.0 =>
+            {
+                Ok(PadZone {
+                    name: name.to_string(),
+                    x,
+                    y,
+                    width,
+                    height,
+                })
+            }
+            _ => Err(format!(
+                "Invalid zone '{}', expected 4 fractions of the screen fitting in it",
+                input
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControlMessage {
+    Hello { version: u8 },
+    Forwarding(bool),
+    Monitor(String),
+    Profile(String),
+    Zones(Vec<PadZone>),
+}
+
+impl ControlMessage {
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        let mut payload = vec![];
+
+        let kind = match self {
+            ControlMessage::Hello { version } => {
+                payload.push(*version);
+                MESSAGE_HELLO
+            }
+            ControlMessage::Forwarding(active) => {
+                payload.push(*active as u8);
+                MESSAGE_FORWARDING
+            }
+            ControlMessage::Monitor(name) => {
+                write_string(&mut payload, name)?;
+                MESSAGE_MONITOR
+            }
+            ControlMessage::Profile(name) => {
+                write_string(&mut payload, name)?;
+                MESSAGE_PROFILE
+            }
+            ControlMessage::Zones(zones) => {
+                payload.extend_from_slice(&encode_length(zones.len(), "zones")?.to_be_bytes());
+                for zone in zones {
+                    write_string(&mut payload, &zone.name)?;
+                    for value in [zone.x, zone.y, zone.width, zone.height] {
+                        payload.extend_from_slice(&value.to_be_bytes());
+                    }
+                }
+                MESSAGE_ZONES
+            }
+        };
+
+        let mut frame = vec![kind];
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&payload);
+        Ok(frame)
+    }
+}
+
+fn encode_length(length: usize, what: &str) -> Result<u16, Error> {
+    u16::try_from(length).map_err(|_| {
+        Error::Protocol(format!(
+            "Too many {} to send to the companion app ({})",
+            what, length
+        ))
+    })
+}
+
+fn write_string(output: &mut Vec<u8>, value: &str) -> Result<(), Error> {
+    output.extend_from_slice(&encode_length(value.len(), "string bytes")?.to_be_bytes());
+    output.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+// Reference decoder for clients, returns None once the bridge closes the connection.
+pub fn read_message(reader: &mut impl Read) -> Result<Option<ControlMessage>, Error> {
+    loop {
+        let mut header = [0u8; 5];
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+
+        let length = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+        let mut payload = vec![0u8; length];
+        reader.read_exact(&mut payload)?;
+        let mut payload = payload.as_slice();
+
+        let message = match header[0] {
+            MESSAGE_HELLO => ControlMessage::Hello {
+                version: take_u8(&mut payload)?,
+            },
+            MESSAGE_FORWARDING => ControlMessage::Forwarding(take_u8(&mut payload)? != 0),
+            MESSAGE_MONITOR => ControlMessage::Monitor(take_string(&mut payload)?),
+            MESSAGE_PROFILE => ControlMessage::Profile(take_string(&mut payload)?),
+            MESSAGE_ZONES => {
+                let mut count = [0u8; 2];
+                payload.read_exact(&mut count)?;
+                let mut zones = vec![];
+                for _ in 0..u16::from_be_bytes(count) {
+                    zones.push(PadZone {
+                        name: take_string(&mut payload)?,
+                        x: take_f32(&mut payload)?,
+                        y: take_f32(&mut payload)?,
+                        width: take_f32(&mut payload)?,
+                        height: take_f32(&mut payload)?,
+                    });
+                }
+                ControlMessage::Zones(zones)
+            }
+            _ => continue,
+        };
+
+        return Ok(Some(message));
+    }
+}
+
+fn take_u8(payload: &mut &[u8]) -> io::Result<u8> {
+    let mut buffer = [0u8; 1];
+    payload.read_exact(&mut buffer)?;
+    Ok(buffer[0])
+}
+
+fn take_f32(payload: &mut &[u8]) -> io::Result<f32> {
+    let mut buffer = [0u8; 4];
+    payload.read_exact(&mut buffer)?;
+    Ok(f32::from_be_bytes(buffer))
+}
+
+fn take_string(payload: &mut &[u8]) -> Result<String, Error> {
+    let mut length = [0u8; 2];
+    payload.read_exact(&mut length)?;
+    let mut value = vec![0u8; u16::from_be_bytes(length) as usize];
+    payload.read_exact(&mut value)?;
+    String::from_utf8(value).map_err(|err| Error::Companion(err.to_string()))
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ControlState {
+    pub forwarding: bool,
+    pub monitor: Option<String>,
+    pub profile: Option<String>,
+    pub zones: Vec<PadZone>,
+}
+
+impl ControlState {
+    fn messages(&self) -> Vec<ControlMessage> {
+        let mut messages = vec![
+            ControlMessage::Hello {
+                version: PROTOCOL_VERSION,
+            },
+            ControlMessage::Forwarding(self.forwarding),
+        ];
+        if let Some(ref monitor) = self.monitor {
+            messages.push(ControlMessage::Monitor(monitor.clone()));
+        }
+        if let Some(ref profile) = self.profile {
+            messages.push(ControlMessage::Profile(profile.clone()));
+        }
+        messages.push(ControlMessage::Zones(self.zones.clone()));
+        messages
+    }
+}
+
+// Sends the whole state to every app connecting, then each change as it happens.
+pub struct ControlServer {
+    address: SocketAddr,
+    state: Mutex<ControlState>,
+    clients: Mutex<Vec<TcpStream>>,
+}
+
+impl ControlServer {
+    pub fn serve(address: SocketAddr, state: ControlState) -> Result<Arc<Self>, Error> {
+        let listener = TcpListener::bind(address)?;
+        let server = Arc::new(ControlServer {
+            address: listener.local_addr()?,
+            state: Mutex::new(state),
+            clients: Mutex::new(vec![]),
+        });
+        info!(
+            "Serving companion app control channel on {}",
+            server.address
+        );
+
+        let accepting = server.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => accepting.add_client(stream),
+                    Err(err) => warn!("Could not accept control connection: {}", err),
+                }
+            }
+        });
+
+        Ok(server)
+    }
+
+    // The clients lock is held from the snapshot until the client is added, so a change is either
+    // in the snapshot or broadcast to the client after it.
+    fn add_client(&self, mut stream: TcpStream) {
+        let Ok(mut clients) = self.clients.lock() else {
+            return;
+        };
+        let snapshot = match self.state.lock() {
+            Ok(state) => state.messages(),
+            Err(_) => return,
+        };
+
+        let _ = stream.set_nodelay(true);
+        let _ = stream.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT));
+        let frames = match snapshot
+            .iter()
+            .map(ControlMessage::encode)
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(frames) => frames.concat(),
+            Err(err) => {
+                warn!("Could not send state to the companion app: {}", err);
+                return;
+            }
+        };
+        if let Err(err) = stream.write_all(&frames) {
+            warn!("Could not send state to the companion app: {}", err);
+            return;
+        }
+
+        clients.push(stream);
+    }
+
+    pub fn set_forwarding(&self, forwarding: bool) {
+        let changed = match self.state.lock() {
+            Ok(mut state) => std::mem::replace(&mut state.forwarding, forwarding) != forwarding,
+            Err(_) => false,
+        };
+
+        if changed {
+            self.broadcast(&ControlMessage::Forwarding(forwarding));
+        }
+    }
+
+    fn broadcast(&self, message: &ControlMessage) {
+        let frame = match message.encode() {
+            Ok(frame) => frame,
+            Err(err) => {
+                warn!("Could not send an update to the companion app: {}", err);
+                return;
+            }
+        };
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.retain_mut(|client| client.write_all(&frame).is_ok());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_parses_pad_zones() {
+        assert_eq!(
+            "undo=0,0.9,0.2,0.1".parse(),
+            Ok(PadZone {
+                name: String::from("undo"),
+                x: 0.0,
+                y: 0.9,
+                width: 0.2,
+                height: 0.1,
+            })
+        );
+        assert!("undo=0.9,0.9,0.2,0.1".parse::<PadZone>().is_err());
+    }
+
+    #[test]
+    fn it_pushes_state_to_companion_app() {
+        let zone: PadZone = "undo=0,0.9,0.2,0.1".parse().unwrap();
+        let state = ControlState {
+            forwarding: false,
+            monitor: Some(String::from("DP-1")),
+            profile: None,
+            zones: vec![zone.clone()],
+        };
+        let server = ControlServer::serve("127.0.0.1:0".parse().unwrap(), state).unwrap();
+        let mut client = TcpStream::connect(server.address).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        let mut received = vec![];
+        for _ in 0..4 {
+            received.push(read_message(&mut client).unwrap().unwrap());
+        }
+        assert_eq!(
+            received,
+            vec![
+                ControlMessage::Hello {
+                    version: PROTOCOL_VERSION
+                },
+                ControlMessage::Forwarding(false),
+                ControlMessage::Monitor(String::from("DP-1")),
+                ControlMessage::Zones(vec![zone]),
+            ]
+        );
+
+        server.set_forwarding(true);
+        server.set_forwarding(true);
+        server.broadcast(&ControlMessage::Profile(String::from("drawing")));
+        assert_eq!(
+            read_message(&mut client).unwrap(),
+            Some(ControlMessage::Forwarding(true))
+        );
+        assert_eq!(
+            read_message(&mut client).unwrap(),
+            Some(ControlMessage::Profile(String::from("drawing")))
+        );
+
+        let mut future = vec![42, 0, 0, 0, 2, 1, 2];
+        future.extend(ControlMessage::Forwarding(false).encode().unwrap());
+        assert_eq!(
+            read_message(&mut future.as_slice()).unwrap(),
+            Some(ControlMessage::Forwarding(false))
+        );
+    }
+
+    #[test]
+    fn it_rejects_oversized_strings() {
+        assert!(ControlMessage::Profile("x".repeat(70_000))
+            .encode()
+            .is_err());
+    }
+}
@@ -0,0 +1,84 @@
+use std::{
+    net::SocketAddrV4,
+    time::{Duration, Instant},
+};
+
+use mdns_sd::{ServiceDaemon, ServiceEvent};
+
+use crate::error::Error;
+
+pub const CONNECT_SERVICE: &str = "_adb-tls-connect._tcp.local.";
+pub const PAIRING_SERVICE: &str = "_adb-tls-pairing._tcp.local.";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiscoveredDevice {
+    pub name: String,
+    pub service: &'static str,
+    pub address: SocketAddrV4,
+}
+
+// Browses the given services for the whole timeout since devices answer at their own pace.
+pub fn discover(
+    services: &[&'static str],
+    timeout: Duration,
+) -> Result<Vec<DiscoveredDevice>, Error> {
+    let daemon = ServiceDaemon::new()?;
+    let receivers = services
+        .iter()
+        .map(|service| Ok((*service, daemon.browse(service)?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let deadline = Instant::now() + timeout;
+    let mut devices: Vec<DiscoveredDevice> = vec![];
+
+    while Instant::now() < deadline {
+        for (service, receiver) in receivers.iter() {
+            while let Ok(event) = receiver.try_recv() {
+                if let ServiceEvent::ServiceResolved(info) = event {
+                    for ip in info.get_addresses_v4() {
+                        let device = DiscoveredDevice {
+                            name: instance_name(info.get_fullname(), service),
+                            service,
+                            address: SocketAddrV4::new(*ip, info.get_port()),
+                        };
+                        if !devices.contains(&device) {
+                            devices.push(device);
+                        }
+                    }
+                }
+            }
+        }
+
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    let _ = daemon.shutdown();
+
+    Ok(devices)
+}
+
+fn instance_name(fullname: &str, service: &str) -> String {
+    fullname
+        .strip_suffix(service)
+        .map(|x| x.trim_end_matches('.'))
+        .unwrap_or(fullname)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_extracts_instance_names() {
+        assert_eq!(
+            instance_name(
+                "adb-R52W70ABCDE-Xyz12a._adb-tls-connect._tcp.local.",
+                CONNECT_SERVICE
+            ),
+            "adb-R52W70ABCDE-Xyz12a"
+        );
+        assert_eq!(instance_name("unexpected", PAIRING_SERVICE), "unexpected");
+    }
+}
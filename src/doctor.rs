@@ -0,0 +1,248 @@
+use std::{
+    fmt,
+    fs::{self, OpenOptions},
+    io,
+    os::unix::fs::{FileTypeExt, MetadataExt},
+    path::Path,
+};
+
+use adb_client::{ADBServer, DeviceState};
+use nix::unistd::{Gid, Group};
+
+use crate::{
+    classify::{classify, DeviceKind},
+    cli::{UINPUT_PATH, UINPUT_PERMISSION_HINT},
+    error::Error,
+    source::{AdbServerSource, EventSource},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    Ok,
+    Warning,
+    Failure,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Check {
+    pub status: Status,
+    pub subject: String,
+    pub detail: String,
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn new(status: Status, subject: impl Into<String>, detail: impl Into<String>) -> Self {
+        Check {
+            status,
+            subject: subject.into(),
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self.status {
+            Status::Ok => "[ok]  ",
+            Status::Warning => "[warn]",
+            Status::Failure => "[fail]",
+        };
+
+        write!(f, "{} {}: {}", label, self.subject, self.detail)?;
+
+        if let Some(ref hint) = self.hint {
+            write!(f, "\n       -> {}", hint)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn run_checks() -> Vec<Check> {
+    let mut checks = vec![check_uinput(Path::new(UINPUT_PATH))];
+    checks.extend(check_adb());
+    checks
+}
+
+pub fn check_uinput(path: &Path) -> Check {
+    let subject = path.display().to_string();
+
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => {
+            return Check::new(Status::Failure, subject, "missing")
+                .hint("load the uinput kernel module with `sudo modprobe uinput`")
+        }
+    };
+
+    let group = Group::from_gid(Gid::from_raw(metadata.gid()))
+        .ok()
+        .flatten()
+        .map(|x| x.name)
+        .unwrap_or_else(|| metadata.gid().to_string());
+    let ownership = format!("group {}, mode {:o}", group, metadata.mode() & 0o777);
+
+    if !metadata.file_type().is_char_device() {
+        return Check::new(
+            Status::Warning,
+            subject,
+            format!("not a character device ({})", ownership),
+        );
+    }
+
+    match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(_) => Check::new(Status::Ok, subject, format!("writable ({})", ownership)),
+        Err(err) if err.kind() == io::ErrorKind::PermissionDenied => Check::new(
+            Status::Failure,
+            subject,
+            format!("not writable by the current user ({})", ownership),
+        )
+        .hint(UINPUT_PERMISSION_HINT),
+        Err(err) => Check::new(Status::Failure, subject, err.to_string()),
+    }
+}
+
+fn check_adb() -> Vec<Check> {
+    let mut server = ADBServer::default();
+
+    let devices = match server.devices_long() {
+        Ok(devices) => devices,
+        Err(err) => {
+            return vec![Check::new(
+                Status::Failure,
+                "ADB server",
+                format!("unreachable: {}", err),
+            )
+            .hint("start it with `adb start-server`")]
+        }
+    };
+
+    let mut checks = vec![Check::new(Status::Ok, "ADB server", "reachable")];
+
+    if devices.is_empty() {
+        checks.push(
+            Check::new(Status::Failure, "Devices", "none connected").hint(
+                "enable USB debugging on the tablet and plug it, or use `adb connect <host>:<port>`",
+            ),
+        );
+    }
+
+    for device in devices {
+        let subject = format!("Device {}", device.identifier);
+
+        match device.state {
+            DeviceState::Device => {
+                checks.push(Check::new(
+                    Status::Ok,
+                    subject.clone(),
+                    format!("authorized ({} {})", device.product, device.model),
+                ));
+            }
+            DeviceState::Unauthorized => {
+                checks.push(
+                    Check::new(Status::Failure, subject, "unauthorized")
+                        .hint("unlock the tablet and accept the USB debugging prompt"),
+                );
+                continue;
+            }
+            DeviceState::Offline => {
+                checks.push(
+                    Check::new(Status::Failure, subject, "offline")
+                        .hint("reconnect the device or run `adb reconnect offline`"),
+                );
+                continue;
+            }
+            DeviceState::NoPerm => {
+                checks.push(
+                    Check::new(Status::Failure, subject, "no permission on the USB device")
+                        .hint("install udev rules for Android devices, e.g. the android-udev-rules package"),
+                );
+                continue;
+            }
+            state => {
+                checks.push(Check::new(
+                    Status::Warning,
+                    subject,
+                    format!("in state {}", state),
+                ));
+                continue;
+            }
+        }
+
+        match AdbServerSource::new(device.identifier).describe() {
+            Ok(subdevices) => {
+                checks.push(Check::new(
+                    Status::Ok,
+                    "getevent",
+                    format!("available, {} subdevices", subdevices.len()),
+                ));
+
+                match subdevices
+                    .iter()
+                    .find(|x| classify(x) == Some(DeviceKind::Pen))
+                {
+                    Some(pen) => checks.push(Check::new(
+                        Status::Ok,
+                        "Pen",
+                        format!("{} ({})", pen.name, pen.path),
+                    )),
+                    None => checks.push(
+                        Check::new(Status::Failure, "Pen", "no subdevice looks like a pen")
+                            .hint("check `list-sub-device` output, the tablet may not have a stylus digitizer"),
+                    ),
+                }
+            }
+            Err(Error::Parse { .. }) => checks.push(
+                Check::new(Status::Failure, "getevent", "missing or unexpected output")
+                    .hint("getevent is part of Android toolbox, check `adb shell getevent -i`"),
+            ),
+            Err(err) => checks.push(Check::new(Status::Failure, "getevent", err.to_string())),
+        }
+    }
+
+    checks
+}
+
+pub fn udev_rule(group: &str) -> String {
+    format!(
+        "# Generated by android-tablet-bridge\n\
+         KERNEL==\"uinput\", SUBSYSTEM==\"misc\", GROUP=\"{}\", MODE=\"0660\", OPTIONS+=\"static_node=uinput\"\n",
+        group
+    )
+}
+
+pub fn group_exists(group: &str) -> bool {
+    Group::from_name(group).ok().flatten().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_diagnoses_uinput_node() {
+        let missing = check_uinput(Path::new("/nonexistent/uinput"));
+        assert_eq!(missing.status, Status::Failure);
+        assert_eq!(
+            missing.hint.as_deref(),
+            Some("load the uinput kernel module with `sudo modprobe uinput`")
+        );
+
+        let regular_file = check_uinput(Path::new("Cargo.toml"));
+        assert_eq!(regular_file.status, Status::Warning);
+
+        assert_eq!(
+            udev_rule("uinput"),
+            "# Generated by android-tablet-bridge\n\
+             KERNEL==\"uinput\", SUBSYSTEM==\"misc\", GROUP=\"uinput\", MODE=\"0660\", OPTIONS+=\"static_node=uinput\"\n"
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use std::str::FromStr;
+
+use input_linux::{
+    AbsoluteAxis, Event, EventTime, Key, KeyEvent, KeyState, SynchronizeEvent, SynchronizeKind,
+};
+
+use crate::control::PadZone;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EraserTrigger {
+    Button(Key),
+    Zone(String),
+}
+
+impl FromStr for EraserTrigger {
+    type Err = String;
+
+    // button:<stylus|stylus2> | zone:<pad zone name>
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.split_once(':') {
+            Some(("button", "stylus")) => Ok(EraserTrigger::Button(Key::ButtonStylus)),
+            Some(("button", "stylus2")) => Ok(EraserTrigger::Button(Key::ButtonStylus2)),
+            Some(("zone", name)) if !name.is_empty() => Ok(EraserTrigger::Zone(name.to_string())),
+            _ => Err(format!(
+                "Expected button:stylus, button:stylus2 or zone:<name>, got '{}'",
+                input
+            )),
+        }
+    }
+}
+
+enum Trigger {
+    Button(Key),
+    Zone(PadZone),
+}
+
+// Turns the pen into an eraser the way Wacom drivers do: the tool leaves proximity and comes
+// back as BTN_TOOL_RUBBER, never in the middle of a stroke. Native eraser ends pass through.
+pub struct EraserSwitch {
+    trigger: Trigger,
+    maximum: (i32, i32),
+    erasing: bool,
+    native: Option<Key>,
+    current: Option<Key>,
+    touching: bool,
+    position: (i32, i32),
+    swallowing_tap: bool,
+}
+
+impl EraserSwitch {
+    pub fn new(
+        trigger: &EraserTrigger,
+        zones: &[PadZone],
+        maximum_x: i32,
+        maximum_y: i32,
+    ) -> Result<Self, String> {
+        let trigger = match trigger {
+            EraserTrigger::Button(key) => Trigger::Button(*key),
+            EraserTrigger::Zone(name) => Trigger::Zone(
+                zones
+                    .iter()
+                    .find(|x| x.name == *name)
+                    .cloned()
+                    .ok_or_else(|| format!("No --pad-zone named '{}'", name))?,
+            ),
+        };
+
+        Ok(EraserSwitch {
+            trigger,
+            maximum: (maximum_x.max(1), maximum_y.max(1)),
+            erasing: false,
+            native: None,
+            current: None,
+            touching: false,
+            position: (0, 0),
+            swallowing_tap: false,
+        })
+    }
+
+    fn tool(&self) -> Key {
+        if self.erasing {
+            Key::ButtonToolRubber
+        } else {
+            Key::ButtonToolPen
+        }
+    }
+
+    fn is_trigger_button(&self, key: Key) -> bool {
+        matches!(self.trigger, Trigger::Button(button) if button == key)
+    }
+
+    fn in_zone(&self) -> bool {
+        match self.trigger {
+            Trigger::Zone(ref zone) => {
+                let x = self.position.0 as f32 / self.maximum.0 as f32;
+                let y = self.position.1 as f32 / self.maximum.1 as f32;
+                x >= zone.x && x <= zone.x + zone.width && y >= zone.y && y <= zone.y + zone.height
+            }
+            Trigger::Button(_) => false,
+        }
+    }
+
+    pub fn process(&mut self, event: Event) -> Vec<Event> {
+        match event {
+            Event::Key(key_event) if key_event.key == Key::ButtonToolPen => {
+                let pressed = key_event.value.is_pressed();
+                let tool = match (pressed, self.current) {
+                    (true, _) => self.tool(),
+                    (false, Some(current)) => current,
+                    (false, None) => Key::ButtonToolPen,
+                };
+                self.native = pressed.then_some(Key::ButtonToolPen);
+                self.current = pressed.then_some(tool);
+                vec![key_event_at(key_event.time, tool, pressed)]
+            }
+            Event::Key(key_event) if key_event.key == Key::ButtonToolRubber => {
+                let pressed = key_event.value.is_pressed();
+                self.native = pressed.then_some(Key::ButtonToolRubber);
+                self.current = self.native;
+                vec![event]
+            }
+            Event::Key(key_event) if self.is_trigger_button(key_event.key) => {
+                self.erasing = key_event.value.is_pressed();
+                vec![]
+            }
+            Event::Key(key_event) if key_event.key == Key::ButtonTouch => {
+                let pressed = key_event.value.is_pressed();
+
+                if pressed && self.native == Some(Key::ButtonToolPen) && self.in_zone() {
+                    self.erasing = !self.erasing;
+                    self.swallowing_tap = true;
+                    return vec![];
+                }
+                if !pressed && self.swallowing_tap {
+                    self.swallowing_tap = false;
+                    return vec![];
+                }
+
+                self.touching = pressed;
+                vec![event]
+            }
+            Event::Absolute(absolute_event) => {
+                match absolute_event.axis {
+                    AbsoluteAxis::X => self.position.0 = absolute_event.value,
+                    AbsoluteAxis::Y => self.position.1 = absolute_event.value,
+                    AbsoluteAxis::Pressure if self.swallowing_tap => return vec![],
+                    _ => {}
+                }
+                vec![event]
+            }
+            Event::Synchronize(sync_event) if sync_event.kind == SynchronizeKind::Report => {
+                let tool = self.tool();
+                match self.current {
+                    Some(current)
+                        if current != tool
+                            && self.native == Some(Key::ButtonToolPen)
+                            && !self.touching =>
+                    {
+                        self.current = Some(tool);
+                        let report = Event::Synchronize(SynchronizeEvent::report(sync_event.time));
+                        vec![
+                            event,
+                            key_event_at(sync_event.time, current, false),
+                            report,
+                            key_event_at(sync_event.time, tool, true),
+                            report,
+                        ]
+                    }
+                    _ => vec![event],
+                }
+            }
+            _ => vec![event],
+        }
+    }
+}
+
+fn key_event_at(time: EventTime, key: Key, pressed: bool) -> Event {
+    Event::Key(KeyEvent::new(time, key, KeyState::pressed(pressed)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input_linux::AbsoluteEvent;
+    use pretty_assertions::assert_eq;
+
+    fn key(key: Key, pressed: bool) -> Event {
+        key_event_at(EventTime::default(), key, pressed)
+    }
+
+    fn absolute(axis: AbsoluteAxis, value: i32) -> Event {
+        Event::Absolute(AbsoluteEvent::new(EventTime::default(), axis, value))
+    }
+
+    fn report() -> Event {
+        Event::Synchronize(SynchronizeEvent::report(EventTime::default()))
+    }
+
+    fn process(switch: &mut EraserSwitch, events: Vec<Event>) -> Vec<Event> {
+        events
+            .into_iter()
+            .flat_map(|event| switch.process(event))
+            .collect()
+    }
+
+    #[test]
+    fn it_switches_to_eraser_between_strokes() {
+        let trigger = "button:stylus".parse::<EraserTrigger>().unwrap();
+        let mut switch = EraserSwitch::new(&trigger, &[], 1000, 1000).unwrap();
+
+        assert_eq!(
+            process(
+                &mut switch,
+                vec![
+                    key(Key::ButtonToolPen, true),
+                    key(Key::ButtonTouch, true),
+                    key(Key::ButtonStylus, true),
+                    report(),
+                ]
+            ),
+            vec![
+                key(Key::ButtonToolPen, true),
+                key(Key::ButtonTouch, true),
+                report()
+            ]
+        );
+        assert_eq!(
+            process(&mut switch, vec![key(Key::ButtonTouch, false), report()]),
+            vec![
+                key(Key::ButtonTouch, false),
+                report(),
+                key(Key::ButtonToolPen, false),
+                report(),
+                key(Key::ButtonToolRubber, true),
+                report(),
+            ]
+        );
+        assert_eq!(
+            process(&mut switch, vec![key(Key::ButtonToolPen, false), report()]),
+            vec![key(Key::ButtonToolRubber, false), report()]
+        );
+
+        let zones = vec!["erase=0,0,0.1,0.1".parse::<PadZone>().unwrap()];
+        let trigger = "zone:erase".parse::<EraserTrigger>().unwrap();
+        assert!(EraserSwitch::new(&"zone:missing".parse().unwrap(), &zones, 1000, 1000).is_err());
+        let mut switch = EraserSwitch::new(&trigger, &zones, 1000, 1000).unwrap();
+
+        assert_eq!(
+            process(
+                &mut switch,
+                vec![
+                    key(Key::ButtonToolPen, true),
+                    absolute(AbsoluteAxis::X, 50),
+                    absolute(AbsoluteAxis::Y, 50),
+                    key(Key::ButtonTouch, true),
+                    absolute(AbsoluteAxis::Pressure, 300),
+                    report(),
+                ]
+            ),
+            vec![
+                key(Key::ButtonToolPen, true),
+                absolute(AbsoluteAxis::X, 50),
+                absolute(AbsoluteAxis::Y, 50),
+                report(),
+                key(Key::ButtonToolPen, false),
+                report(),
+                key(Key::ButtonToolRubber, true),
+                report(),
+            ]
+        );
+        assert_eq!(
+            process(&mut switch, vec![key(Key::ButtonTouch, false), report()]),
+            vec![report()]
+        );
+        assert_eq!(
+            process(
+                &mut switch,
+                vec![
+                    key(Key::ButtonToolRubber, true),
+                    key(Key::ButtonToolRubber, false)
+                ]
+            ),
+            vec![
+                key(Key::ButtonToolRubber, true),
+                key(Key::ButtonToolRubber, false)
+            ]
+        );
+    }
+}
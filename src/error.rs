@@ -0,0 +1,67 @@
+use std::{io, path::PathBuf};
+
+use adb_client::RustADBError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("ADB request failed: {0} (is the ADB server running and the device authorized? check `adb devices`)")]
+    Adb(#[from] RustADBError),
+
+    #[error("Could not parse {context}: {message}")]
+    Parse {
+        context: &'static str,
+        message: String,
+    },
+
+    #[error("Permission denied on {}: {hint}", path.display())]
+    Permission { path: PathBuf, hint: &'static str },
+
+    #[error("{} does not exist: load the uinput kernel module with `sudo modprobe uinput`", path.display())]
+    UinputMissing { path: PathBuf },
+
+    #[error("uinput request failed: {0}")]
+    Uinput(#[source] io::Error),
+
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("{0}")]
+    InvalidArguments(String),
+
+    #[error("ADB protocol error: {0}")]
+    Protocol(String),
+
+    #[error("Remote command failed: {0}")]
+    Command(String),
+
+    #[error("Companion app protocol error: {0}")]
+    Companion(String),
+
+    #[error("Screen capture failed: {0}")]
+    Capture(String),
+
+    #[error("mDNS discovery failed: {0}")]
+    Discovery(#[from] mdns_sd::Error),
+
+    #[error("Could not serialize output: {0}")]
+    Serialize(String),
+
+    #[error("{0} check(s) failed")]
+    ChecksFailed(usize),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl Error {
+    pub fn parse(context: &'static str, err: impl std::fmt::Display) -> Self {
+        Error::Parse {
+            context,
+            message: err.to_string(),
+        }
+    }
+
+    pub fn serialize(err: impl std::fmt::Display) -> Self {
+        Error::Serialize(err.to_string())
+    }
+}
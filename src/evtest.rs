@@ -0,0 +1,396 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_till1, take_until, take_while1},
+    character::{
+        self,
+        complete::{alpha1, char, hex_digit1, i32, i64, newline, not_line_ending, space0, space1},
+    },
+    combinator::{map_opt, map_res, not, opt},
+    multi::many0,
+    sequence::{delimited, preceded, separated_pair, terminated, tuple},
+    IResult, Parser,
+};
+
+use input_linux::{
+    AbsoluteAxis, AbsoluteInfo, AbsoluteInfoSetup, Event, EventKind, EventTime, InputEvent,
+    InputProperty, Key, RelativeAxis, SwitchKind,
+};
+
+use crate::{
+    labels::{self, code},
+    parser::{parse_device_blocks, ADBDevice, ADBDeviceEvents, ADBDeviceId, SkippedDevice},
+};
+
+enum EvtestHeader<'a> {
+    Id(ADBDeviceId),
+    Name(&'a str),
+    Other,
+}
+
+struct EvtestCode {
+    code: u16,
+    state: Option<i32>,
+    fields: Vec<(&'static str, i32)>,
+}
+
+const ABSOLUTE_FIELDS: [&str; 6] = ["Value", "Min", "Max", "Fuzz", "Flat", "Resolution"];
+
+pub fn is_evtest_description(input: &str) -> bool {
+    input.contains("\nSupported events:\n")
+}
+
+// evtest describes one device at a time, so descriptions are evtest outputs each preceded by the
+// device path like evtest lists devices ("/dev/input/event5:\tWacom Intuos Pro M Pen"). Events
+// printed after "Testing ..." are ignored.
+pub fn parse_evtest_devices_lenient(input: &str) -> (Vec<ADBDevice>, Vec<SkippedDevice>) {
+    let mut blocks: Vec<String> = vec![];
+    let mut in_block = false;
+
+    for line in input.split_inclusive('\n') {
+        if line.starts_with("/dev/input/") {
+            blocks.push(line.to_string());
+            in_block = true;
+        } else if line.starts_with("Testing ") {
+            in_block = false;
+        } else if in_block {
+            if let Some(block) = blocks.last_mut() {
+                block.push_str(line);
+            }
+        }
+    }
+
+    parse_device_blocks(blocks, parse_evtest_device)
+}
+
+// Only the event kinds getevent streams are parsed into are returned, like parse_input_event.
+pub fn parse_evtest_event(input: &str) -> IResult<&str, Option<Event>> {
+    let (input, _) = tag("Event: time ")(input)?;
+    let (input, (seconds, microseconds)) = separated_pair(i64, char('.'), i64)(input)?;
+    let (input, _) = tag(", ")(input)?;
+
+    let (input, (kind, code, value)) = alt((
+        tuple((
+            terminated(parse_numbered("type"), tag(", ")),
+            terminated(parse_numbered("code"), tag(", value ")),
+            i32,
+        )),
+        // -------------- SYN_REPORT ------------
+        preceded(
+            terminated(take_while1(|c: char| c.is_ascii_punctuation()), space1),
+            map_opt(
+                take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_'),
+                |label| code(labels::SYNCHRONIZE, label),
+            ),
+        )
+        .map(|code| (0, code, 0)),
+    ))
+    .parse(input)?;
+    let (input, _) = not_line_ending(input)?;
+
+    let event = EventKind::from_type(kind)
+        .ok()
+        .filter(|x| {
+            matches!(
+                x,
+                EventKind::Synchronize | EventKind::Key | EventKind::Absolute
+            )
+        })
+        .and_then(|kind| {
+            Event::new(InputEvent {
+                time: EventTime::new(seconds, microseconds),
+                kind,
+                code,
+                value,
+            })
+            .ok()
+        });
+
+    Ok((input, event))
+}
+
+fn parse_evtest_device(input: &str) -> IResult<&str, ADBDevice> {
+    let (input, path) = take_till1(|c| c == ':')(input)?;
+    let (input, _) = terminated(not_line_ending, newline)(input)?;
+    let (input, headers) = many0(parse_evtest_header)(input)?;
+    let (input, _) = tag("Supported events:\n")(input)?;
+    let (input, kinds) = many0(parse_evtest_event_kind)(input)?;
+    let (input, _) = opt(preceded(
+        tag("Key repeat handling:\n"),
+        many0(terminated(preceded(space1, not_line_ending), newline)),
+    ))(input)?;
+    let (input, properties) =
+        opt(preceded(tag("Properties:\n"), many0(parse_evtest_property)))(input)?;
+
+    let mut name = "";
+    let mut id = None;
+
+    for header in headers {
+        match header {
+            EvtestHeader::Id(value) => id = Some(value),
+            EvtestHeader::Name(value) => name = value,
+            EvtestHeader::Other => {}
+        }
+    }
+
+    let mut events = ADBDeviceEvents {
+        keys: vec![],
+        relative: vec![],
+        absolute: vec![],
+        switches: vec![],
+    };
+
+    // Codes input-linux does not know are dropped
+    for (kind, codes) in kinds {
+        match EventKind::from_type(kind) {
+            Ok(EventKind::Key) => events
+                .keys
+                .extend(codes.iter().filter_map(|x| Key::from_code(x.code).ok())),
+            Ok(EventKind::Relative) => events.relative.extend(
+                codes
+                    .iter()
+                    .filter_map(|x| RelativeAxis::from_code(x.code).ok()),
+            ),
+            Ok(EventKind::Absolute) => events.absolute.extend(codes.iter().filter_map(|x| {
+                let field = |label| {
+                    x.fields
+                        .iter()
+                        .find(|(name, _)| *name == label)
+                        .map(|(_, value)| *value)
+                        .unwrap_or_default()
+                };
+                AbsoluteAxis::from_code(x.code)
+                    .ok()
+                    .map(|axis| AbsoluteInfoSetup {
+                        axis,
+                        info: AbsoluteInfo {
+                            value: field("Value"),
+                            minimum: field("Min"),
+                            maximum: field("Max"),
+                            fuzz: field("Fuzz"),
+                            flat: field("Flat"),
+                            resolution: field("Resolution"),
+                        },
+                    })
+            })),
+            Ok(EventKind::Switch) => events.switches.extend(codes.iter().filter_map(|x| {
+                SwitchKind::from_code(x.code)
+                    .ok()
+                    .map(|switch| (switch, x.state.unwrap_or_default() != 0))
+            })),
+            _ => {}
+        }
+    }
+
+    Ok((
+        input,
+        ADBDevice {
+            path: path.to_owned(),
+            name: name.to_owned(),
+            id,
+            events,
+            properties: properties
+                .unwrap_or_default()
+                .into_iter()
+                .flatten()
+                .collect(),
+        },
+    ))
+}
+
+// The driver version, input id and name lines
+fn parse_evtest_header(input: &str) -> IResult<&str, EvtestHeader<'_>> {
+    let (input, _) = not(alt((tag("Supported events:"), space1)))(input)?;
+    alt((
+        delimited(tag("Input device ID: "), parse_evtest_id, newline).map(EvtestHeader::Id),
+        delimited(
+            tag("Input device name: \""),
+            take_until("\""),
+            terminated(char('"'), newline),
+        )
+        .map(EvtestHeader::Name),
+        terminated(not_line_ending, newline).map(|_| EvtestHeader::Other),
+    ))(input)
+}
+
+// bus 0x3 vendor 0x56a product 0x357 version 0x110
+fn parse_evtest_id(input: &str) -> IResult<&str, ADBDeviceId> {
+    let field = |label| preceded(terminated(tag(label), tag(" 0x")), parse_hex);
+    let (input, bustype) = field("bus")(input)?;
+    let (input, vendor) = preceded(space1, field("vendor"))(input)?;
+    let (input, product) = preceded(space1, field("product"))(input)?;
+    let (input, version) = preceded(space1, field("version"))(input)?;
+
+    Ok((
+        input,
+        ADBDeviceId {
+            bustype,
+            vendor,
+            product,
+            version,
+            ..ADBDeviceId::default()
+        },
+    ))
+}
+
+fn parse_evtest_event_kind(input: &str) -> IResult<&str, (u16, Vec<EvtestCode>)> {
+    let (input, kind) = delimited(
+        tag("  "),
+        parse_numbered("Event type"),
+        terminated(space0, newline),
+    )(input)?;
+    let (input, codes) = many0(parse_evtest_code)(input)?;
+    Ok((input, (kind, codes)))
+}
+
+// Absolute axes are followed by their value, range, fuzz, flat and resolution, switches and
+// LEDs by their state.
+fn parse_evtest_code(input: &str) -> IResult<&str, EvtestCode> {
+    let (input, code) = preceded(tag("    "), parse_numbered("Event code"))(input)?;
+    let (input, state) = opt(preceded(tag(" state "), i32))(input)?;
+    let (input, _) = terminated(space0, newline)(input)?;
+    let (input, fields) = many0(delimited(
+        tag("      "),
+        separated_pair(
+            map_opt(alpha1, |label: &str| {
+                ABSOLUTE_FIELDS.iter().find(|x| **x == label).copied()
+            }),
+            space1,
+            i32,
+        ),
+        terminated(space0, newline),
+    ))(input)?;
+
+    Ok((
+        input,
+        EvtestCode {
+            code,
+            state,
+            fields,
+        },
+    ))
+}
+
+fn parse_evtest_property(input: &str) -> IResult<&str, Option<InputProperty>> {
+    let (input, property) = delimited(
+        tag("  "),
+        parse_numbered("Property type"),
+        terminated(space0, newline),
+    )(input)?;
+    Ok((input, InputProperty::from_code(property).ok()))
+}
+
+// A decimal number followed by its name, "type 3 (EV_ABS)" or "code 0 (?)"
+fn parse_numbered<'a>(label: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, u16> {
+    move |input| {
+        let (input, _) = terminated(tag(label), space1)(input)?;
+        let (input, number) = character::complete::u16(input)?;
+        let (input, _) = opt(preceded(
+            space1,
+            delimited(char('('), take_until(")"), char(')')),
+        ))(input)?;
+        Ok((input, number))
+    }
+}
+
+fn parse_hex(input: &str) -> IResult<&str, u16> {
+    map_res(hex_digit1, |x| u16::from_str_radix(x, 16))(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        classify::{classify, DeviceKind},
+        parser::{parse_devices, parse_devices_lenient, parse_input_event},
+    };
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_parses_evtest_output() {
+        let data = include_str!("../devices-evtest.txt");
+
+        assert_eq!(
+            parse_devices(data),
+            Err(String::from(
+                "/dev/input/event7:\tBroken Touchscreen: unexpected line \"Max      ???\""
+            ))
+        );
+
+        let (devices, _) = parse_devices_lenient(data);
+        assert_eq!(
+            devices
+                .iter()
+                .map(|x| (x.path.as_str(), x.name.as_str(), classify(x)))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "/dev/input/event5",
+                    "Wacom Intuos Pro M Pen",
+                    Some(DeviceKind::Pen)
+                ),
+                ("/dev/input/event0", "Lid Switch", None),
+                (
+                    "/dev/input/event3",
+                    "AT Translated Set 2 keyboard",
+                    Some(DeviceKind::Keyboard)
+                ),
+            ]
+        );
+        assert_eq!(
+            devices[0].id,
+            Some(ADBDeviceId {
+                bustype: 3,
+                vendor: 0x56a,
+                product: 0x357,
+                version: 0x110,
+                ..ADBDeviceId::default()
+            })
+        );
+        assert_eq!(
+            devices[0].events.absolute[4],
+            AbsoluteInfoSetup {
+                axis: AbsoluteAxis::TiltX,
+                info: AbsoluteInfo {
+                    value: 0,
+                    minimum: -64,
+                    maximum: 63,
+                    fuzz: 0,
+                    flat: 0,
+                    resolution: 57,
+                },
+            }
+        );
+        assert_eq!(devices[0].properties, vec![InputProperty::Direct]);
+        assert_eq!(devices[1].events.switches, vec![(SwitchKind::Lid, false)]);
+        assert_eq!(devices[2].events.keys.len(), 8);
+
+        // The same events as getevent prints them
+        let events: Vec<Option<Event>> = include_str!("../events-evtest.txt")
+            .lines()
+            .map(|line| parse_evtest_event(line).unwrap().1)
+            .collect();
+        let expected: Vec<Option<Event>> = [
+            "[1700000000.120003] 0001 0140 00000001",
+            "[1700000000.120003] 0003 0000 0000578a",
+            "[1700000000.120003] 0003 0001 000039c6",
+            "[1700000000.120003] 0003 0019 0000001f",
+            "[1700000000.120003] 0003 001a fffffff4",
+            "[1700000000.120003] 0004 0000 2c4ef0a1",
+            "[1700000000.120003] 0000 0000 00000000",
+            "[1700000000.127512] 0001 014a 00000001",
+            "[1700000000.127512] 0003 0018 000004b4",
+            "[1700000000.127512] 0003 0019 00000000",
+            "[1700000000.127512] 0000 0000 00000000",
+            "[1700000000.135009] 0000 0003 00000000",
+            "[1700000000.142511] 0003 0018 00000000",
+            "[1700000000.142511] 0001 014a 00000000",
+            "[1700000000.142511] 0000 0000 00000000",
+            "[1700000000.150000] 0001 0140 00000000",
+            "[1700000000.150000] 0000 0000 00000000",
+        ]
+        .iter()
+        .map(|line| parse_input_event(line).unwrap().1)
+        .collect();
+        assert_eq!(events, expected);
+    }
+}
@@ -0,0 +1,683 @@
+use std::{f64::consts::PI, io, process::Command, str::FromStr, thread, time::Instant};
+
+use clap::ValueEnum;
+use input_linux::{
+    AbsoluteAxis, Event, EventTime, Key, KeyEvent, KeyState, RelativeAxis, RelativeEvent,
+    SynchronizeEvent, SynchronizeKind,
+};
+
+use crate::{
+    cli::Rotation,
+    output::OutputDevice,
+    parser::{parse_key_name, ADBDevice},
+};
+
+const TAP_TIMEOUT_US: i64 = 250_000;
+const LONG_PRESS_US: i64 = 600_000;
+const SLOP: f64 = 0.02;
+const SWIPE_DISTANCE: f64 = 0.12;
+const PINCH_STEP: f64 = 0.15;
+const ROTATE_STEP: f64 = PI / 12.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Gesture {
+    TwoFingerTap,
+    LongPress,
+    PinchIn,
+    PinchOut,
+    RotateClockwise,
+    RotateCounterClockwise,
+    ThreeFingerSwipeUp,
+    ThreeFingerSwipeDown,
+    ThreeFingerSwipeLeft,
+    ThreeFingerSwipeRight,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GestureAction {
+    Keys(Vec<Key>),
+    Scroll(RelativeAxis, i32),
+    Command(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GestureBinding {
+    pub gesture: Gesture,
+    pub action: GestureAction,
+}
+
+impl FromStr for GestureBinding {
+    type Err = String;
+
+    // <gesture>=key:LeftCtrl+Z | scroll:<wheel|hwheel>:<amount> | command:<shell command>
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (gesture, action) = input
+            .split_once('=')
+            .ok_or_else(|| format!("Expected <gesture>=<action>, got '{}'", input))?;
+
+        Ok(GestureBinding {
+            gesture: Gesture::from_str(gesture, true)?,
+            action: action.parse()?,
+        })
+    }
+}
+
+impl FromStr for GestureAction {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.split_once(':') {
+            Some(("key", chord)) => {
+                let keys = chord
+                    .split('+')
+                    .map(|name| {
+                        parse_key_name(name).ok_or_else(|| format!("Unknown key '{}'", name))
+                    })
+                    .collect::<Result<Vec<Key>, String>>()?;
+                Ok(GestureAction::Keys(keys))
+            }
+            Some(("scroll", scroll)) => {
+                let (axis, amount) = scroll.split_once(':').ok_or_else(|| {
+                    format!("Expected scroll:<wheel|hwheel>:<amount>, got '{}'", input)
+                })?;
+                let axis = match axis {
+                    "wheel" => RelativeAxis::Wheel,
+                    "hwheel" => RelativeAxis::HorizontalWheel,
+                    _ => return Err(format!("Unknown scroll axis '{}'", axis)),
+                };
+                let amount = amount
+                    .parse()
+                    .map_err(|_| format!("Invalid scroll amount '{}'", amount))?;
+                Ok(GestureAction::Scroll(axis, amount))
+            }
+            Some(("command", command)) => Ok(GestureAction::Command(command.to_owned())),
+            _ => Err(format!(
+                "Unknown action '{}', expected key:, scroll: or command:",
+                input
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Contact {
+    active: bool,
+    x: i32,
+    y: i32,
+}
+
+struct Session {
+    started_at: i64,
+    max_contacts: usize,
+    contacts: usize,
+    origins: Vec<Option<(f64, f64)>>,
+    moved: bool,
+    fired: bool,
+    reference: Option<(f64, f64)>,
+    swipe_origin: Option<(f64, f64)>,
+}
+
+impl Session {
+    fn long_press_due(&self, time: i64) -> bool {
+        self.contacts == 1
+            && !self.moved
+            && !self.fired
+            && self.max_contacts == 1
+            && time - self.started_at >= LONG_PRESS_US
+    }
+}
+
+pub struct GestureRecognizer {
+    scale: f64,
+    rotation: Option<Rotation>,
+    slots: Vec<Contact>,
+    slot: usize,
+    session: Option<Session>,
+    // Event time of the last frame and when it was received, to follow the device clock between
+    // frames
+    last_frame: Option<(i64, Instant)>,
+}
+
+impl GestureRecognizer {
+    pub fn new(device: &ADBDevice, rotation: Option<Rotation>) -> Self {
+        let maximum = device
+            .events
+            .absolute
+            .iter()
+            .filter(|setup| {
+                setup.axis == AbsoluteAxis::MultitouchPositionX
+                    || setup.axis == AbsoluteAxis::MultitouchPositionY
+            })
+            .map(|setup| setup.info.maximum)
+            .max()
+            .unwrap_or(1);
+
+        GestureRecognizer {
+            scale: maximum.max(1) as f64,
+            rotation,
+            slots: vec![Contact::default()],
+            slot: 0,
+            session: None,
+            last_frame: None,
+        }
+    }
+
+    pub fn process(&mut self, event: &Event, received_at: Instant) -> Vec<Gesture> {
+        match event {
+            Event::Absolute(absolute_event) => {
+                match absolute_event.axis {
+                    AbsoluteAxis::MultitouchSlot => {
+                        self.slot = absolute_event.value.max(0) as usize;
+                    }
+                    AbsoluteAxis::MultitouchTrackingId => {
+                        self.contact().active = absolute_event.value >= 0;
+                    }
+                    AbsoluteAxis::MultitouchPositionX => self.contact().x = absolute_event.value,
+                    AbsoluteAxis::MultitouchPositionY => self.contact().y = absolute_event.value,
+                    _ => {}
+                }
+                vec![]
+            }
+            Event::Synchronize(sync_event) if sync_event.kind == SynchronizeKind::Report => {
+                let time = event_time_us(&sync_event.time);
+                self.last_frame = Some((time, received_at));
+                self.process_frame(time)
+            }
+            _ => vec![],
+        }
+    }
+
+    // Digitizers usually stop sending frames while a finger is held still, so the long press is
+    // also checked between frames. Returns the gestures with the device time they happened at.
+    pub fn tick(&mut self, now: Instant) -> (EventTime, Vec<Gesture>) {
+        let Some((last_time, last_received_at)) = self.last_frame else {
+            return (EventTime::new(0, 0), vec![]);
+        };
+        let time = last_time + now.saturating_duration_since(last_received_at).as_micros() as i64;
+        let event_time = EventTime::new(time / 1_000_000, time % 1_000_000);
+
+        match self.session.as_mut() {
+            Some(session) if session.long_press_due(time) => {
+                session.fired = true;
+                (event_time, vec![Gesture::LongPress])
+            }
+            _ => (event_time, vec![]),
+        }
+    }
+
+    fn contact(&mut self) -> &mut Contact {
+        if self.slots.len() <= self.slot {
+            self.slots.resize(self.slot + 1, Contact::default());
+        }
+        &mut self.slots[self.slot]
+    }
+
+    fn process_frame(&mut self, time: i64) -> Vec<Gesture> {
+        let positions: Vec<Option<(f64, f64)>> = self
+            .slots
+            .iter()
+            .map(|contact| {
+                contact
+                    .active
+                    .then(|| (contact.x as f64 / self.scale, contact.y as f64 / self.scale))
+            })
+            .collect();
+        let contacts: Vec<(f64, f64)> = positions.iter().flatten().copied().collect();
+
+        if contacts.is_empty() {
+            return match self.session.take() {
+                Some(session)
+                    if !session.moved
+                        && !session.fired
+                        && session.max_contacts == 2
+                        && time - session.started_at < TAP_TIMEOUT_US =>
+                {
+                    vec![Gesture::TwoFingerTap]
+                }
+                _ => vec![],
+            };
+        }
+
+        let session = self.session.get_or_insert_with(|| Session {
+            started_at: time,
+            max_contacts: 0,
+            contacts: 0,
+            origins: vec![],
+            moved: false,
+            fired: false,
+            reference: None,
+            swipe_origin: None,
+        });
+
+        if session.origins.len() < positions.len() {
+            session.origins.resize(positions.len(), None);
+        }
+
+        for (origin, position) in session.origins.iter_mut().zip(positions.iter()) {
+            match (*origin, position) {
+                (None, Some(position)) => *origin = Some(*position),
+                (Some(_), None) => *origin = None,
+                (Some(start), Some(position)) if distance(start, *position) > SLOP => {
+                    session.moved = true;
+                }
+                _ => {}
+            }
+        }
+
+        if session.contacts != contacts.len() {
+            session.contacts = contacts.len();
+            session.reference = None;
+            session.swipe_origin = None;
+        }
+        session.max_contacts = session.max_contacts.max(contacts.len());
+
+        let mut gestures = vec![];
+
+        match contacts.len() {
+            1 if session.long_press_due(time) => gestures.push(Gesture::LongPress),
+            2 => {
+                let span = distance(contacts[0], contacts[1]);
+                let angle = (contacts[1].1 - contacts[0].1).atan2(contacts[1].0 - contacts[0].0);
+
+                match session.reference {
+                    None => session.reference = Some((span, angle)),
+                    Some((reference_span, reference_angle)) => {
+                        let mut reference = (reference_span, reference_angle);
+
+                        if reference_span > 0.0 && span / reference_span >= 1.0 + PINCH_STEP {
+                            gestures.push(Gesture::PinchOut);
+                            reference.0 = span;
+                        } else if span > 0.0 && reference_span / span >= 1.0 + PINCH_STEP {
+                            gestures.push(Gesture::PinchIn);
+                            reference.0 = span;
+                        }
+
+                        let mut delta = angle - reference_angle;
+                        if delta > PI {
+                            delta -= 2.0 * PI;
+                        } else if delta < -PI {
+                            delta += 2.0 * PI;
+                        }
+
+                        if delta >= ROTATE_STEP {
+                            gestures.push(Gesture::RotateClockwise);
+                            reference.1 = angle;
+                        } else if delta <= -ROTATE_STEP {
+                            gestures.push(Gesture::RotateCounterClockwise);
+                            reference.1 = angle;
+                        }
+
+                        session.reference = Some(reference);
+                    }
+                }
+            }
+            3 => {
+                let centroid = (
+                    contacts.iter().map(|contact| contact.0).sum::<f64>() / 3.0,
+                    contacts.iter().map(|contact| contact.1).sum::<f64>() / 3.0,
+                );
+
+                match session.swipe_origin {
+                    None => session.swipe_origin = Some(centroid),
+                    Some(origin)
+                        if !session.fired && distance(origin, centroid) > SWIPE_DISTANCE =>
+                    {
+                        session.fired = true;
+                        gestures.push(swipe_gesture(
+                            &self.rotation,
+                            centroid.0 - origin.0,
+                            centroid.1 - origin.1,
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+
+        if !gestures.is_empty() {
+            session.fired = true;
+        }
+
+        gestures
+    }
+}
+
+pub fn perform_gesture_action(
+    uhandle: &OutputDevice,
+    action: &GestureAction,
+    time: EventTime,
+) -> Result<(), io::Error> {
+    let report = SynchronizeEvent::report(time).into_event().into_raw();
+
+    match action {
+        GestureAction::Keys(keys) => {
+            for key in keys {
+                let event = KeyEvent::new(time, *key, KeyState::PRESSED);
+                uhandle.write(&[event.into_event().into_raw()])?;
+            }
+            uhandle.write(&[report])?;
+            for key in keys.iter().rev() {
+                let event = KeyEvent::new(time, *key, KeyState::RELEASED);
+                uhandle.write(&[event.into_event().into_raw()])?;
+            }
+            uhandle.write(&[report])?;
+        }
+        GestureAction::Scroll(axis, amount) => {
+            let event = RelativeEvent::new(time, *axis, *amount);
+            uhandle.write(&[event.into_event().into_raw(), report])?;
+        }
+        GestureAction::Command(command) => {
+            if let Some(output) = uhandle.dry_run_output() {
+                let mut output = output.lock().unwrap_or_else(|err| err.into_inner());
+                return writeln!(output, "run command: {}", command);
+            }
+
+            let mut child = Command::new("sh").arg("-c").arg(command).spawn()?;
+            // Reaped in the background so commands do not pile up as zombies
+            thread::spawn(move || child.wait());
+        }
+    }
+
+    Ok(())
+}
+
+fn swipe_gesture(rotation: &Option<Rotation>, dx: f64, dy: f64) -> Gesture {
+    let (dx, dy) = match rotation {
+        None => (dx, dy),
+        Some(Rotation::Rotation90) => (-dy, dx),
+        Some(Rotation::Rotation180) => (-dx, -dy),
+        Some(Rotation::Rotation270) => (dy, -dx),
+    };
+
+    if dx.abs() > dy.abs() {
+        if dx > 0.0 {
+            Gesture::ThreeFingerSwipeRight
+        } else {
+            Gesture::ThreeFingerSwipeLeft
+        }
+    } else if dy > 0.0 {
+        Gesture::ThreeFingerSwipeDown
+    } else {
+        Gesture::ThreeFingerSwipeUp
+    }
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (b.0 - a.0).hypot(b.1 - a.1)
+}
+
+fn event_time_us(time: &EventTime) -> i64 {
+    time.seconds() * 1_000_000 + time.microseconds()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input_linux::{AbsoluteEvent, AbsoluteInfo, AbsoluteInfoSetup, InputProperty};
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    use crate::parser::ADBDeviceEvents;
+
+    fn touchscreen() -> ADBDevice {
+        let info = AbsoluteInfo {
+            value: 0,
+            minimum: 0,
+            maximum: 1000,
+            fuzz: 0,
+            flat: 0,
+            resolution: 0,
+        };
+
+        ADBDevice {
+            path: String::from("/dev/input/event4"),
+            name: String::from("sec_touchscreen"),
+            id: None,
+            events: ADBDeviceEvents {
+                keys: vec![],
+                relative: vec![],
+                absolute: vec![
+                    AbsoluteInfoSetup {
+                        axis: AbsoluteAxis::MultitouchPositionX,
+                        info,
+                    },
+                    AbsoluteInfoSetup {
+                        axis: AbsoluteAxis::MultitouchPositionY,
+                        info,
+                    },
+                ],
+                switches: vec![],
+            },
+            properties: vec![InputProperty::Direct],
+        }
+    }
+
+    // Feeds a frame where each entry is (slot, Some((x, y))) for a contact or (slot, None) for a lift.
+    fn frame(
+        recognizer: &mut GestureRecognizer,
+        start: Instant,
+        time_ms: i64,
+        contacts: &[(i32, Option<(i32, i32)>)],
+    ) -> Vec<Gesture> {
+        let time = EventTime::new(time_ms / 1000, (time_ms % 1000) * 1000);
+        let mut gestures = vec![];
+        let mut events = vec![];
+
+        for (slot, position) in contacts {
+            events.push(AbsoluteEvent::new(
+                time,
+                AbsoluteAxis::MultitouchSlot,
+                *slot,
+            ));
+            match position {
+                None => events.push(AbsoluteEvent::new(
+                    time,
+                    AbsoluteAxis::MultitouchTrackingId,
+                    -1,
+                )),
+                Some((x, y)) => {
+                    events.push(AbsoluteEvent::new(
+                        time,
+                        AbsoluteAxis::MultitouchTrackingId,
+                        *slot,
+                    ));
+                    events.push(AbsoluteEvent::new(
+                        time,
+                        AbsoluteAxis::MultitouchPositionX,
+                        *x,
+                    ));
+                    events.push(AbsoluteEvent::new(
+                        time,
+                        AbsoluteAxis::MultitouchPositionY,
+                        *y,
+                    ));
+                }
+            }
+        }
+
+        let received_at = start + Duration::from_millis(time_ms as u64);
+        for event in events {
+            gestures.extend(recognizer.process(&Event::Absolute(event), received_at));
+        }
+        gestures.extend(recognizer.process(
+            &Event::Synchronize(SynchronizeEvent::report(time)),
+            received_at,
+        ));
+        gestures
+    }
+
+    #[test]
+    fn it_recognizes_two_finger_tap() {
+        let mut recognizer = GestureRecognizer::new(&touchscreen(), None);
+        let start = Instant::now();
+
+        assert_eq!(
+            frame(
+                &mut recognizer,
+                start,
+                0,
+                &[(0, Some((100, 100))), (1, Some((300, 100)))]
+            ),
+            vec![]
+        );
+        assert_eq!(
+            frame(&mut recognizer, start, 100, &[(0, None), (1, None)]),
+            vec![Gesture::TwoFingerTap]
+        );
+    }
+
+    #[test]
+    fn it_ignores_slow_two_finger_touch() {
+        let mut recognizer = GestureRecognizer::new(&touchscreen(), None);
+        let start = Instant::now();
+
+        frame(
+            &mut recognizer,
+            start,
+            0,
+            &[(0, Some((100, 100))), (1, Some((300, 100)))],
+        );
+        assert_eq!(
+            frame(&mut recognizer, start, 400, &[(0, None), (1, None)]),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn it_recognizes_long_press_once() {
+        let mut recognizer = GestureRecognizer::new(&touchscreen(), None);
+        let start = Instant::now();
+
+        assert_eq!(
+            frame(&mut recognizer, start, 0, &[(0, Some((500, 500)))]),
+            vec![]
+        );
+        assert_eq!(
+            frame(&mut recognizer, start, 700, &[(0, Some((502, 501)))]),
+            vec![Gesture::LongPress]
+        );
+        assert_eq!(
+            frame(&mut recognizer, start, 800, &[(0, Some((502, 501)))]),
+            vec![]
+        );
+        assert_eq!(frame(&mut recognizer, start, 900, &[(0, None)]), vec![]);
+    }
+
+    #[test]
+    fn it_recognizes_long_press_without_frames() {
+        let mut recognizer = GestureRecognizer::new(&touchscreen(), None);
+        let start = Instant::now();
+
+        assert_eq!(
+            frame(&mut recognizer, start, 0, &[(0, Some((500, 500)))]),
+            vec![]
+        );
+        assert_eq!(
+            recognizer.tick(start + Duration::from_millis(300)),
+            (EventTime::new(0, 300_000), vec![])
+        );
+        assert_eq!(
+            recognizer.tick(start + Duration::from_millis(650)),
+            (EventTime::new(0, 650_000), vec![Gesture::LongPress])
+        );
+        assert_eq!(
+            recognizer.tick(start + Duration::from_millis(800)).1,
+            vec![]
+        );
+        assert_eq!(frame(&mut recognizer, start, 900, &[(0, None)]), vec![]);
+    }
+
+    #[test]
+    fn it_recognizes_pinch_and_rotation() {
+        let mut recognizer = GestureRecognizer::new(&touchscreen(), None);
+        let start = Instant::now();
+
+        frame(
+            &mut recognizer,
+            start,
+            0,
+            &[(0, Some((400, 500))), (1, Some((600, 500)))],
+        );
+        assert_eq!(
+            frame(
+                &mut recognizer,
+                start,
+                50,
+                &[(0, Some((300, 500))), (1, Some((700, 500)))]
+            ),
+            vec![Gesture::PinchOut]
+        );
+        assert_eq!(
+            frame(
+                &mut recognizer,
+                start,
+                100,
+                &[(0, Some((500, 300))), (1, Some((500, 700)))]
+            ),
+            vec![Gesture::RotateClockwise]
+        );
+        assert_eq!(
+            frame(&mut recognizer, start, 150, &[(0, None), (1, None)]),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn it_recognizes_rotated_three_finger_swipe() {
+        let mut recognizer = GestureRecognizer::new(&touchscreen(), Some(Rotation::Rotation90));
+        let start = Instant::now();
+
+        frame(
+            &mut recognizer,
+            start,
+            0,
+            &[
+                (0, Some((100, 500))),
+                (1, Some((200, 500))),
+                (2, Some((300, 500))),
+            ],
+        );
+        assert_eq!(
+            frame(
+                &mut recognizer,
+                start,
+                50,
+                &[
+                    (0, Some((300, 500))),
+                    (1, Some((400, 500))),
+                    (2, Some((500, 500))),
+                ],
+            ),
+            vec![Gesture::ThreeFingerSwipeDown]
+        );
+    }
+
+    #[test]
+    fn it_parses_gesture_bindings() {
+        assert_eq!(
+            "two-finger-tap=key:LeftCtrl+z".parse(),
+            Ok(GestureBinding {
+                gesture: Gesture::TwoFingerTap,
+                action: GestureAction::Keys(vec![Key::LeftCtrl, Key::Z]),
+            })
+        );
+        assert_eq!(
+            "pinch-in=scroll:wheel:-1".parse(),
+            Ok(GestureBinding {
+                gesture: Gesture::PinchIn,
+                action: GestureAction::Scroll(RelativeAxis::Wheel, -1),
+            })
+        );
+        assert_eq!(
+            "long-press=command:notify-send 'long press'".parse(),
+            Ok(GestureBinding {
+                gesture: Gesture::LongPress,
+                action: GestureAction::Command(String::from("notify-send 'long press'")),
+            })
+        );
+        assert!("two-finger-tap=key:NotAKey"
+            .parse::<GestureBinding>()
+            .is_err());
+    }
+}
@@ -0,0 +1,289 @@
+use clap::ValueEnum;
+use input_linux::{AbsoluteAxis, InputId, Key};
+
+use crate::{error::Error, parser::ADBDevice};
+
+#[derive(Clone, Debug, PartialEq, ValueEnum)]
+pub enum IdentityPreset {
+    Generic,
+    WacomIntuosPro,
+    Custom,
+    // The input id getevent -i reports for the Android subdevice
+    Clone,
+}
+
+#[derive(Clone, Debug, PartialEq, ValueEnum)]
+pub enum BusType {
+    Usb,
+    Bluetooth,
+    I2c,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct IdentityArgs {
+    #[arg(long, value_enum, default_value_t = IdentityPreset::Generic)]
+    identity: IdentityPreset,
+
+    #[arg(long, value_parser = parse_hex_u16, required_if_eq("identity", "custom"))]
+    identity_vendor: Option<u16>,
+
+    #[arg(long, value_parser = parse_hex_u16, required_if_eq("identity", "custom"))]
+    identity_product: Option<u16>,
+
+    #[arg(long, value_parser = parse_hex_u16)]
+    identity_version: Option<u16>,
+
+    #[arg(long, value_enum)]
+    identity_bustype: Option<BusType>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceIdentity {
+    pub name: String,
+    pub bustype: u16,
+    pub vendor: u16,
+    pub product: u16,
+    pub version: u16,
+}
+
+impl IdentityArgs {
+    pub fn resolve(
+        &self,
+        name_opt: Option<String>,
+        device: &ADBDevice,
+    ) -> Result<DeviceIdentity, Error> {
+        let preset = match self.identity {
+            IdentityPreset::Generic | IdentityPreset::Custom => DeviceIdentity {
+                name: String::from("Android Tablet Bridge"),
+                bustype: BusType::Usb.code(),
+                vendor: 0x1234,
+                product: 0x5678,
+                version: 0,
+            },
+            IdentityPreset::WacomIntuosPro => DeviceIdentity {
+                name: String::from("Wacom Intuos Pro M Pen"),
+                bustype: BusType::Usb.code(),
+                vendor: 0x056a,
+                product: 0x0357,
+                version: 0x0110,
+            },
+            IdentityPreset::Clone => {
+                let id = device.id.as_ref().ok_or_else(|| {
+                    Error::NotFound(format!(
+                        "getevent did not report the input id of {}, pick another --identity",
+                        device.name
+                    ))
+                })?;
+                DeviceIdentity {
+                    name: device.name.clone(),
+                    bustype: id.bustype,
+                    vendor: id.vendor,
+                    product: id.product,
+                    version: id.version,
+                }
+            }
+        };
+
+        Ok(DeviceIdentity {
+            name: name_opt.unwrap_or(preset.name),
+            bustype: self
+                .identity_bustype
+                .as_ref()
+                .map(BusType::code)
+                .unwrap_or(preset.bustype),
+            vendor: self.identity_vendor.unwrap_or(preset.vendor),
+            product: self.identity_product.unwrap_or(preset.product),
+            version: self.identity_version.unwrap_or(preset.version),
+        })
+    }
+}
+
+impl BusType {
+    fn code(&self) -> u16 {
+        match self {
+            BusType::Usb => input_linux::sys::BUS_USB,
+            BusType::Bluetooth => input_linux::sys::BUS_BLUETOOTH,
+            BusType::I2c => input_linux::sys::BUS_I2C,
+        }
+    }
+}
+
+impl DeviceIdentity {
+    pub fn with_suffix(&self, suffix: &str) -> DeviceIdentity {
+        DeviceIdentity {
+            name: format!("{} {}", self.name, suffix),
+            ..self.clone()
+        }
+    }
+
+    pub fn input_id(&self) -> InputId {
+        InputId {
+            bustype: self.bustype,
+            vendor: self.vendor,
+            product: self.product,
+            version: self.version,
+        }
+    }
+
+    fn bus_name(&self) -> &'static str {
+        match self.bustype {
+            input_linux::sys::BUS_USB => "usb",
+            input_linux::sys::BUS_BLUETOOTH => "bluetooth",
+            input_linux::sys::BUS_I2C => "i2c",
+            input_linux::sys::BUS_RS232 => "serial",
+            _ => "unknown",
+        }
+    }
+}
+
+pub fn libwacom_tablet_file(
+    identity: &DeviceIdentity,
+    device: &ADBDevice,
+    fallback_resolution: i32,
+) -> String {
+    let size_inches = |axis: AbsoluteAxis| {
+        device
+            .events
+            .absolute
+            .iter()
+            .find(|x| x.axis == axis)
+            .map(|x| {
+                let resolution = if x.info.resolution == 0 {
+                    fallback_resolution
+                } else {
+                    x.info.resolution
+                };
+                (x.info.maximum - x.info.minimum) as f64 / resolution as f64 / 25.4
+            })
+            .unwrap_or(0.0)
+    };
+
+    let styli = if device.events.keys.contains(&Key::ButtonToolRubber) {
+        "@generic-with-eraser"
+    } else {
+        "@generic-no-eraser"
+    };
+
+    format!(
+        "# Generated by android-tablet-bridge for {}\n\
+         [Device]\n\
+         Name={}\n\
+         ModelName=\n\
+         DeviceMatch={}|{:04x}|{:04x};\n\
+         Width={:.0}\n\
+         Height={:.0}\n\
+         Styli={};\n\
+         \n\
+         [Features]\n\
+         Stylus=true\n\
+         Reversible=false\n\
+         Touch=false\n\
+         Buttons=0\n",
+        device.name,
+        identity.name,
+        identity.bus_name(),
+        identity.vendor,
+        identity.product,
+        size_inches(AbsoluteAxis::X),
+        size_inches(AbsoluteAxis::Y),
+        styli,
+    )
+}
+
+pub fn hwdb_file(identity: &DeviceIdentity) -> String {
+    format!(
+        "# Generated by android-tablet-bridge\n\
+         evdev:input:b{:04X}v{:04X}p{:04X}*\n \
+         ID_INPUT=1\n \
+         ID_INPUT_TABLET=1\n \
+         ID_INPUT_JOYSTICK=0\n",
+        identity.bustype, identity.vendor, identity.product,
+    )
+}
+
+fn parse_hex_u16(input: &str) -> Result<u16, String> {
+    let digits = input
+        .strip_prefix("0x")
+        .or_else(|| input.strip_prefix("0X"))
+        .unwrap_or(input);
+    u16::from_str_radix(digits, 16).map_err(|err| format!("Invalid hexadecimal value: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    use crate::parser::{parse_devices, parse_devices_lenient};
+
+    fn wacom_identity() -> DeviceIdentity {
+        DeviceIdentity {
+            name: String::from("Wacom Intuos Pro M Pen"),
+            bustype: BusType::Usb.code(),
+            vendor: 0x056a,
+            product: 0x0357,
+            version: 0x0110,
+        }
+    }
+
+    #[test]
+    fn it_generates_hwdb_file() {
+        assert_eq!(
+            hwdb_file(&wacom_identity()),
+            "# Generated by android-tablet-bridge\n\
+             evdev:input:b0003v056Ap0357*\n \
+             ID_INPUT=1\n \
+             ID_INPUT_TABLET=1\n \
+             ID_INPUT_JOYSTICK=0\n"
+        );
+    }
+
+    #[test]
+    fn it_generates_libwacom_tablet_file() {
+        let devices = parse_devices(include_str!("../devices.txt")).unwrap();
+        let device = devices.iter().find(|x| x.name == "sec_e-pen").unwrap();
+
+        let tablet_file = libwacom_tablet_file(&wacom_identity(), device, 100);
+
+        assert!(tablet_file.contains("DeviceMatch=usb|056a|0357;\n"));
+        assert!(tablet_file.contains("Width=8\n"));
+        assert!(tablet_file.contains("Height=12\n"));
+        assert!(tablet_file.contains("Styli=@generic-with-eraser;\n"));
+    }
+
+    #[derive(clap::Parser)]
+    struct TestCli {
+        #[command(flatten)]
+        identity: IdentityArgs,
+    }
+
+    #[test]
+    fn it_clones_device_input_id() {
+        use clap::Parser;
+
+        let devices = parse_devices(include_str!("../devices.txt")).unwrap();
+        let args = TestCli::parse_from(["test", "--identity", "clone"]).identity;
+        assert!(args.resolve(None, &devices[0]).is_err());
+
+        let (devices, _) = parse_devices_lenient(include_str!("../devices-labelled.txt"));
+        let args = TestCli::parse_from(["test", "--identity", "clone", "--identity-version", "2"])
+            .identity;
+        assert_eq!(
+            args.resolve(None, &devices[1]).unwrap(),
+            DeviceIdentity {
+                name: String::from("gpio_keys"),
+                bustype: 0x19,
+                vendor: 1,
+                product: 1,
+                version: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn it_parses_hex_values() {
+        assert_eq!(parse_hex_u16("0x056a"), Ok(0x056a));
+        assert_eq!(parse_hex_u16("5678"), Ok(0x5678));
+        assert!(parse_hex_u16("xyz").is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+use input_linux::{Event, InputEvent, SynchronizeKind};
+
+use crate::{
+    labels::{self, event_labels, label},
+    parser::ADBDevice,
+};
+
+const BAR_WIDTH: usize = 32;
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+struct AxisState {
+    name: String,
+    code: u16,
+    value: i32,
+    minimum: i32,
+    maximum: i32,
+}
+
+// Live state of a subdevice, rendered as a full screen like evtest but updated in place.
+pub struct Inspector {
+    title: String,
+    axes: Vec<AxisState>,
+    keys: Vec<(String, u16, bool)>,
+    switches: Vec<(String, u16, bool)>,
+    frame: Vec<InputEvent>,
+    last_frame: Vec<InputEvent>,
+    received: VecDeque<Instant>,
+    events: u64,
+    dropped: u64,
+}
+
+impl Inspector {
+    pub fn new(device: &ADBDevice) -> Self {
+        Inspector {
+            title: format!("{} ({})", device.name, device.path),
+            axes: device
+                .events
+                .absolute
+                .iter()
+                .map(|x| AxisState {
+                    name: label(labels::ABSOLUTE, x.axis as u16),
+                    code: x.axis as u16,
+                    value: x.info.value,
+                    minimum: x.info.minimum,
+                    maximum: x.info.maximum,
+                })
+                .collect(),
+            keys: device
+                .events
+                .keys
+                .iter()
+                .map(|x| (label(labels::KEYS, *x as u16), *x as u16, false))
+                .collect(),
+            switches: device
+                .events
+                .switches
+                .iter()
+                .map(|(x, active)| (label(labels::SWITCHES, *x as u16), *x as u16, *active))
+                .collect(),
+            frame: vec![],
+            last_frame: vec![],
+            received: VecDeque::new(),
+            events: 0,
+            dropped: 0,
+        }
+    }
+
+    pub fn process(&mut self, event: Event, received_at: Instant) {
+        self.events += 1;
+        self.received.push_back(received_at);
+        while self
+            .received
+            .front()
+            .is_some_and(|x| received_at.duration_since(*x) > RATE_WINDOW)
+        {
+            self.received.pop_front();
+        }
+
+        match event {
+            Event::Absolute(event) => {
+                if let Some(axis) = self.axes.iter_mut().find(|x| x.code == event.axis as u16) {
+                    axis.value = event.value;
+                }
+            }
+            Event::Key(event) => {
+                if let Some(key) = self.keys.iter_mut().find(|x| x.1 == event.key as u16) {
+                    key.2 = event.value.is_pressed();
+                }
+            }
+            Event::Switch(event) => {
+                if let Some(switch) = self
+                    .switches
+                    .iter_mut()
+                    .find(|x| x.1 == event.switch as u16)
+                {
+                    switch.2 = event.value != 0;
+                }
+            }
+            Event::Synchronize(event) if event.kind == SynchronizeKind::Dropped => {
+                self.dropped += 1;
+            }
+            _ => {}
+        }
+
+        self.frame.push(event.into());
+        if let Event::Synchronize(event) = event {
+            if event.kind == SynchronizeKind::Report {
+                self.last_frame = std::mem::take(&mut self.frame);
+            }
+        }
+    }
+
+    pub fn render(&self, now: Instant) -> String {
+        let rate = self
+            .received
+            .iter()
+            .filter(|x| now.duration_since(**x) <= RATE_WINDOW)
+            .count();
+        let mut screen = String::new();
+
+        let _ = writeln!(
+            screen,
+            "{}\n{} events, {}/s, {} dropped\n",
+            self.title, self.events, rate, self.dropped
+        );
+
+        for axis in &self.axes {
+            // In i64 as full range axes overflow i32
+            let range = (axis.maximum as i64 - axis.minimum as i64).max(1) as f64;
+            let ratio = ((axis.value as i64 - axis.minimum as i64) as f64 / range).clamp(0.0, 1.0);
+            let filled = (ratio * BAR_WIDTH as f64).round() as usize;
+            let _ = writeln!(
+                screen,
+                "{:<24} [{}{}] {:>7} ({}..{})",
+                axis.name,
+                "#".repeat(filled),
+                " ".repeat(BAR_WIDTH - filled),
+                axis.value,
+                axis.minimum,
+                axis.maximum
+            );
+        }
+
+        for (name, _, pressed) in &self.keys {
+            let _ = writeln!(
+                screen,
+                "{:<24} {}",
+                name,
+                if *pressed { "pressed" } else { "-" }
+            );
+        }
+
+        for (name, _, active) in &self.switches {
+            let _ = writeln!(
+                screen,
+                "{:<24} {}",
+                name,
+                if *active { "on" } else { "off" }
+            );
+        }
+
+        let _ = writeln!(screen, "\nLast frame:");
+        for event in &self.last_frame {
+            let (kind, code) = event_labels(event);
+            let _ = writeln!(screen, "  {:<8} {:<24} {}", kind, code, event.value);
+        }
+
+        screen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_devices, parse_input_event};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_renders_subdevice_state() {
+        let devices = parse_devices(include_str!("../devices.txt")).unwrap();
+        let device = devices.iter().find(|x| x.name == "sec_e-pen").unwrap();
+        let mut inspector = Inspector::new(device);
+
+        let start = Instant::now();
+        for (index, line) in [
+            "[ 1.000000] 0001 0140 00000001",
+            "[ 1.000000] 0003 0000 00001324",
+            "[ 1.000000] 0003 0018 00000400",
+            "[ 1.000000] 0000 0000 00000000",
+            "[ 1.000000] 0003 0018 00000800",
+            "[ 1.000000] 0000 0000 00000000",
+            "[ 1.000000] 0003 0018 00000000",
+        ]
+        .iter()
+        .enumerate()
+        {
+            let (_, event) = parse_input_event(line).unwrap();
+            inspector.process(
+                event.unwrap(),
+                start + Duration::from_millis(index as u64 * 300),
+            );
+        }
+
+        let screen = inspector.render(start + Duration::from_millis(1900));
+        let lines: Vec<&str> = screen.lines().collect();
+
+        assert_eq!(lines[0], "sec_e-pen (/dev/input/event9)");
+        assert_eq!(lines[1], "7 events, 4/s, 0 dropped");
+        assert_eq!(
+            lines[3],
+            "ABS_X                    [########                        ]    4900 (0..19589)"
+        );
+        assert_eq!(
+            lines[5],
+            "ABS_PRESSURE             [                                ]       0 (0..4095)"
+        );
+        assert!(lines.contains(&"BTN_TOOL_PEN             pressed"));
+        assert!(lines.contains(&"BTN_TOUCH                -"));
+        assert!(lines.contains(&"SW_PEN_INSERTED          on"));
+        assert_eq!(
+            &lines[lines.len() - 2..],
+            &[
+                "  EV_ABS   ABS_PRESSURE             2048",
+                "  EV_SYN   SYN_REPORT               0"
+            ]
+        );
+
+        inspector.axes[0].minimum = i32::MIN;
+        inspector.axes[0].maximum = i32::MAX;
+        let screen = inspector.render(start);
+        assert_eq!(
+            screen.lines().nth(3),
+            Some("ABS_X                    [################                ]    4900 (-2147483648..2147483647)")
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use adb_client::DeviceLong;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{
+    classify::classify,
+    error::Error,
+    labels::{self, label},
+    parser::ADBDevice,
+};
+
+#[derive(Clone, Debug, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Yaml,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SerializedFormat {
+    Json,
+    Yaml,
+}
+
+impl OutputFormat {
+    // None for tables, which are printed with cli_table
+    pub fn serialized(&self) -> Option<SerializedFormat> {
+        match self {
+            OutputFormat::Table => None,
+            OutputFormat::Json => Some(SerializedFormat::Json),
+            OutputFormat::Yaml => Some(SerializedFormat::Yaml),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DeviceEntry {
+    pub identifier: String,
+    pub state: String,
+    pub usb: String,
+    pub product: String,
+    pub model: String,
+    pub device: String,
+    pub transport_id: u32,
+}
+
+impl From<DeviceLong> for DeviceEntry {
+    fn from(device: DeviceLong) -> Self {
+        DeviceEntry {
+            identifier: device.identifier,
+            state: device.state.to_string(),
+            usb: device.usb,
+            product: device.product,
+            model: device.model,
+            device: device.device,
+            transport_id: device.transport_id,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct SubdeviceEntry {
+    pub source: String,
+    pub name: String,
+    pub path: String,
+    pub kind: Option<String>,
+    pub input_id: Option<InputIdEntry>,
+    pub keys: Vec<String>,
+    pub relative: Vec<String>,
+    pub absolute: Vec<AxisEntry>,
+    pub switches: Vec<SwitchEntry>,
+    pub properties: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct InputIdEntry {
+    pub bustype: u16,
+    pub vendor: u16,
+    pub product: u16,
+    pub version: u16,
+    pub location: String,
+    pub unique_id: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct AxisEntry {
+    pub axis: String,
+    pub value: i32,
+    pub minimum: i32,
+    pub maximum: i32,
+    pub fuzz: i32,
+    pub flat: i32,
+    pub resolution: i32,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct SwitchEntry {
+    pub switch: String,
+    pub active: bool,
+}
+
+impl SubdeviceEntry {
+    pub fn new(source: String, device: &ADBDevice) -> Self {
+        SubdeviceEntry {
+            source,
+            name: device.name.clone(),
+            path: device.path.clone(),
+            kind: classify(device)
+                .and_then(|x| x.to_possible_value())
+                .map(|x| x.get_name().to_string()),
+            input_id: device.id.as_ref().map(|id| InputIdEntry {
+                bustype: id.bustype,
+                vendor: id.vendor,
+                product: id.product,
+                version: id.version,
+                location: id.location.clone(),
+                unique_id: id.unique_id.clone(),
+            }),
+            keys: device
+                .events
+                .keys
+                .iter()
+                .map(|x| label(labels::KEYS, *x as u16))
+                .collect(),
+            relative: device
+                .events
+                .relative
+                .iter()
+                .map(|x| label(labels::RELATIVE, *x as u16))
+                .collect(),
+            absolute: device
+                .events
+                .absolute
+                .iter()
+                .map(|x| AxisEntry {
+                    axis: label(labels::ABSOLUTE, x.axis as u16),
+                    value: x.info.value,
+                    minimum: x.info.minimum,
+                    maximum: x.info.maximum,
+                    fuzz: x.info.fuzz,
+                    flat: x.info.flat,
+                    resolution: x.info.resolution,
+                })
+                .collect(),
+            switches: device
+                .events
+                .switches
+                .iter()
+                .map(|(switch, active)| SwitchEntry {
+                    switch: label(labels::SWITCHES, *switch as u16),
+                    active: *active,
+                })
+                .collect(),
+            properties: device
+                .properties
+                .iter()
+                .map(|x| label(labels::PROPERTIES, *x as u16))
+                .collect(),
+        }
+    }
+}
+
+pub fn format_entries<T: Serialize>(
+    format: SerializedFormat,
+    entries: &[T],
+) -> Result<String, Error> {
+    match format {
+        SerializedFormat::Json => serde_json::to_string_pretty(entries)
+            .map(|x| x + "\n")
+            .map_err(Error::serialize),
+        SerializedFormat::Yaml => serde_yaml::to_string(entries).map_err(Error::serialize),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_devices;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_formats_subdevices() {
+        let devices = parse_devices(include_str!("../devices.txt")).unwrap();
+        let entries: Vec<SubdeviceEntry> = devices
+            .iter()
+            .filter(|x| x.name == "sec_e-pen" || x.name == "meta_event")
+            .map(|x| SubdeviceEntry::new(String::from("R52W"), x))
+            .collect();
+
+        assert_eq!(entries[0].kind.as_deref(), Some("pen"));
+        assert_eq!(
+            entries[0].keys,
+            vec![
+                "KEY_HOMEPAGE",
+                "00fd",
+                "BTN_TOOL_PEN",
+                "BTN_TOOL_RUBBER",
+                "BTN_TOUCH",
+                "BTN_STYLUS"
+            ]
+        );
+
+        let yaml = format_entries(SerializedFormat::Yaml, &entries[1..]).unwrap();
+        assert_eq!(
+            yaml,
+            "- source: R52W\n  \
+               name: meta_event\n  \
+               path: /dev/input/event2\n  \
+               kind: null\n  \
+               input_id: null\n  \
+               keys: []\n  \
+               relative:\n  \
+               - REL_HWHEEL\n  \
+               - REL_DIAL\n  \
+               absolute: []\n  \
+               switches: []\n  \
+               properties: []\n"
+        );
+
+        let json = format_entries(SerializedFormat::Json, &entries[..1]).unwrap();
+        assert!(json.contains(
+            "        \"axis\": \"ABS_TILT_X\",\n        \"value\": -11,\n        \"minimum\": -63,"
+        ));
+        assert!(json.contains("\"switch\": \"SW_PEN_INSERTED\",\n        \"active\": true"));
+    }
+}
@@ -0,0 +1,131 @@
+use std::{
+    io::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use clap::ValueEnum;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde::Serialize;
+
+#[derive(Clone, Debug, PartialEq, ValueEnum)]
+pub enum LogFormat {
+    Auto,
+    Text,
+    Json,
+    Journald,
+}
+
+#[derive(Serialize)]
+struct JsonRecord<'a> {
+    timestamp: f64,
+    level: String,
+    target: &'a str,
+    message: &'a str,
+}
+
+struct Logger {
+    level: LevelFilter,
+    format: LogFormat,
+}
+
+pub fn init(level: LevelFilter, format: LogFormat) {
+    let format = match format {
+        LogFormat::Auto if std::env::var_os("JOURNAL_STREAM").is_some() => LogFormat::Journald,
+        LogFormat::Auto => LogFormat::Text,
+        format => format,
+    };
+
+    if log::set_boxed_logger(Box::new(Logger { level, format })).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let line = format_record(&self.format, record, SystemTime::now());
+            let _ = writeln!(std::io::stderr().lock(), "{}", line);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn format_record(format: &LogFormat, record: &Record, time: SystemTime) -> String {
+    let target = record.target();
+    let message = record.args().to_string();
+
+    match format {
+        LogFormat::Json => {
+            let timestamp = time
+                .duration_since(UNIX_EPOCH)
+                .map(|x| x.as_secs_f64())
+                .unwrap_or(0.0);
+            let record = JsonRecord {
+                timestamp,
+                level: record.level().as_str().to_lowercase(),
+                target,
+                message: &message,
+            };
+            serde_json::to_string(&record).unwrap_or_default()
+        }
+        // journald reads the syslog priority from the <N> prefix and timestamps lines itself.
+        LogFormat::Journald => format!(
+            "<{}>{}: {}",
+            syslog_priority(record.level()),
+            target,
+            message
+        ),
+        LogFormat::Text | LogFormat::Auto => {
+            format!("{:<5} {}: {}", record.level(), target, message)
+        }
+    }
+}
+
+fn syslog_priority(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    fn format(format: LogFormat, level: Level, message: &str) -> String {
+        format_record(
+            &format,
+            &Record::builder()
+                .level(level)
+                .target("android_tablet_bridge::cli")
+                .args(format_args!("{}", message))
+                .build(),
+            UNIX_EPOCH + Duration::from_millis(1500),
+        )
+    }
+
+    #[test]
+    fn it_formats_records() {
+        assert_eq!(
+            format(LogFormat::Text, Level::Warn, "no pen"),
+            "WARN  android_tablet_bridge::cli: no pen"
+        );
+        assert_eq!(
+            format(LogFormat::Journald, Level::Error, "no pen"),
+            "<3>android_tablet_bridge::cli: no pen"
+        );
+        assert_eq!(
+            format(LogFormat::Json, Level::Info, "say \"hi\"\n\u{1b}\u{1f58a}"),
+            "{\"timestamp\":1.5,\"level\":\"info\",\"target\":\"android_tablet_bridge::cli\",\"message\":\"say \\\"hi\\\"\\n\\u001b\u{1f58a}\"}"
+        );
+    }
+}
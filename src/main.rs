@@ -1,8 +1,41 @@
+mod adb_native;
+mod classify;
 mod cli;
+mod command;
+mod companion;
+mod control;
+mod discovery;
+mod doctor;
+mod eraser;
+mod error;
+mod evtest;
+mod gesture;
+mod identity;
+mod inspect;
+mod labels;
+mod listing;
+mod logging;
+mod metrics;
+mod output;
+mod palm;
 mod parser;
+mod pen;
+mod relative;
+mod replay;
+mod sidecar;
+mod signals;
+mod source;
+mod stats;
+mod tilt;
 
-use std::io::{self};
+use std::process::ExitCode;
 
-fn main() -> Result<(), io::Error> {
-    cli::run()
+fn main() -> ExitCode {
+    match cli::run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            log::error!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,271 @@
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use input_linux::{Event, EventKind};
+
+use crate::pen::PenPhase;
+
+const PREFIX: &str = "android_tablet_bridge";
+const EVENT_KINDS: usize = 0x20;
+const LATENCY_BUCKETS_MS: [f64; 10] =
+    [1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1000.0];
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REQUEST_SIZE: u64 = 16 * 1024;
+
+pub struct Metrics {
+    events: [AtomicU64; EVENT_KINDS],
+    parse_errors: AtomicU64,
+    reconnects: AtomicU64,
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS_MS.len()],
+    latency_sum_us: AtomicU64,
+    latency_count: AtomicU64,
+    connected_streams: AtomicI64,
+    pen_phase: AtomicU64,
+    rotation_degrees: u32,
+}
+
+impl Metrics {
+    pub fn new(rotation_degrees: u32) -> Self {
+        Metrics {
+            events: std::array::from_fn(|_| AtomicU64::new(0)),
+            parse_errors: AtomicU64::new(0),
+            reconnects: AtomicU64::new(0),
+            latency_buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            latency_sum_us: AtomicU64::new(0),
+            latency_count: AtomicU64::new(0),
+            connected_streams: AtomicI64::new(0),
+            pen_phase: AtomicU64::new(0),
+            rotation_degrees,
+        }
+    }
+
+    pub fn record_forwarded(&self, event: &Event) {
+        let kind = event.as_event().kind as usize;
+        if let Some(counter) = self.events.get(kind) {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_latency(&self, latency_ms: f64) {
+        for (bucket, upper_bound) in self.latency_buckets.iter().zip(LATENCY_BUCKETS_MS) {
+            if latency_ms <= upper_bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.latency_sum_us
+            .fetch_add((latency_ms.max(0.0) * 1000.0) as u64, Ordering::Relaxed);
+        self.latency_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_parse_error(&self) {
+        self.parse_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_reconnect(&self) {
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_connected(&self, connected: bool) {
+        self.connected_streams
+            .fetch_add(if connected { 1 } else { -1 }, Ordering::Relaxed);
+    }
+
+    pub fn set_pen_phase(&self, phase: PenPhase) {
+        self.pen_phase.store(phase as u64, Ordering::Relaxed);
+    }
+
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+
+        let _ = writeln!(output, "# TYPE {PREFIX}_events counter");
+        let _ = writeln!(output, "# HELP {PREFIX}_events Events forwarded per type.");
+        for (code, counter) in self.events.iter().enumerate() {
+            let count = counter.load(Ordering::Relaxed);
+            if let (true, Ok(kind)) = (count > 0, EventKind::from_type(code as u16)) {
+                let name = format!("{:?}", kind).to_lowercase();
+                let _ = writeln!(output, "{PREFIX}_events_total{{type=\"{name}\"}} {count}");
+            }
+        }
+
+        let _ = writeln!(output, "# TYPE {PREFIX}_parse_errors counter");
+        let _ = writeln!(
+            output,
+            "# HELP {PREFIX}_parse_errors Lines that could not be parsed."
+        );
+        let _ = writeln!(
+            output,
+            "{PREFIX}_parse_errors_total {}",
+            self.parse_errors.load(Ordering::Relaxed)
+        );
+
+        let _ = writeln!(output, "# TYPE {PREFIX}_reconnects counter");
+        let _ = writeln!(
+            output,
+            "# HELP {PREFIX}_reconnects Event stream reconnections."
+        );
+        let _ = writeln!(
+            output,
+            "{PREFIX}_reconnects_total {}",
+            self.reconnects.load(Ordering::Relaxed)
+        );
+
+        let _ = writeln!(output, "# TYPE {PREFIX}_latency_seconds histogram");
+        let _ = writeln!(
+            output,
+            "# HELP {PREFIX}_latency_seconds Delay between the device event timestamp and its reception."
+        );
+        for (bucket, upper_bound) in self.latency_buckets.iter().zip(LATENCY_BUCKETS_MS) {
+            let _ = writeln!(
+                output,
+                "{PREFIX}_latency_seconds_bucket{{le=\"{}\"}} {}",
+                upper_bound / 1000.0,
+                bucket.load(Ordering::Relaxed)
+            );
+        }
+        let count = self.latency_count.load(Ordering::Relaxed);
+        let _ = writeln!(
+            output,
+            "{PREFIX}_latency_seconds_bucket{{le=\"+Inf\"}} {count}"
+        );
+        let _ = writeln!(
+            output,
+            "{PREFIX}_latency_seconds_sum {}",
+            self.latency_sum_us.load(Ordering::Relaxed) as f64 / 1_000_000.0
+        );
+        let _ = writeln!(output, "{PREFIX}_latency_seconds_count {count}");
+
+        let _ = writeln!(output, "# TYPE {PREFIX}_device_connected gauge");
+        let _ = writeln!(
+            output,
+            "# HELP {PREFIX}_device_connected Whether an event stream from the device is open."
+        );
+        let _ = writeln!(
+            output,
+            "{PREFIX}_device_connected {}",
+            (self.connected_streams.load(Ordering::Relaxed) > 0) as u8
+        );
+
+        let _ = writeln!(output, "# TYPE {PREFIX}_pen_state stateset");
+        let _ = writeln!(
+            output,
+            "# HELP {PREFIX}_pen_state Pen state as forwarded to the host."
+        );
+        let pen_phase = self.pen_phase.load(Ordering::Relaxed);
+        for (phase, name) in [
+            (PenPhase::OutOfRange, "out_of_range"),
+            (PenPhase::Hovering, "hovering"),
+            (PenPhase::Touching, "touching"),
+        ] {
+            let _ = writeln!(
+                output,
+                "{PREFIX}_pen_state{{{PREFIX}_pen_state=\"{name}\"}} {}",
+                (phase as u64 == pen_phase) as u8
+            );
+        }
+
+        let _ = writeln!(output, "# TYPE {PREFIX}_rotation_degrees gauge");
+        let _ = writeln!(
+            output,
+            "# HELP {PREFIX}_rotation_degrees Configured rotation."
+        );
+        let _ = writeln!(
+            output,
+            "{PREFIX}_rotation_degrees {}",
+            self.rotation_degrees
+        );
+
+        output.push_str("# EOF\n");
+        output
+    }
+}
+
+// Returns the bound address, port 0 picks a free port
+pub fn serve_metrics(metrics: Arc<Metrics>, address: SocketAddr) -> Result<SocketAddr, io::Error> {
+    let listener = TcpListener::bind(address)?;
+    let address = listener.local_addr()?;
+
+    std::thread::spawn(move || {
+        // Each scrape is answered on its own thread so a stalled client does not block others
+        for stream in listener.incoming().map_while(Result::ok) {
+            let metrics = metrics.clone();
+            std::thread::spawn(move || {
+                if let Err(err) = respond(&metrics, stream) {
+                    log::warn!("Could not serve metrics: {}", err);
+                }
+            });
+        }
+    });
+
+    Ok(address)
+}
+
+fn respond(metrics: &Metrics, stream: TcpStream) -> Result<(), io::Error> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+
+    let mut reader = BufReader::new(stream.try_clone()?.take(MAX_REQUEST_SIZE));
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 && line != "\r\n" && line != "\n" {
+        line.clear();
+    }
+
+    let body = metrics.render();
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: application/openmetrics-text; version=1.0.0; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {}",
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input_linux::{EventTime, Key, KeyEvent, KeyState};
+    use std::io::Read;
+
+    #[test]
+    fn it_serves_openmetrics_text() {
+        let metrics = Arc::new(Metrics::new(90));
+        metrics.record_forwarded(&Event::Key(KeyEvent::new(
+            EventTime::default(),
+            Key::ButtonTouch,
+            KeyState::PRESSED,
+        )));
+        metrics.record_latency(7.0);
+        metrics.set_connected(true);
+        metrics.set_pen_phase(PenPhase::Hovering);
+
+        let address = serve_metrics(metrics, "127.0.0.1:0".parse().unwrap()).unwrap();
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("android_tablet_bridge_events_total{type=\"key\"} 1\n"));
+        assert!(response.contains("android_tablet_bridge_latency_seconds_bucket{le=\"0.005\"} 0\n"));
+        assert!(response.contains("android_tablet_bridge_latency_seconds_bucket{le=\"0.01\"} 1\n"));
+        assert!(response.contains("android_tablet_bridge_device_connected 1\n"));
+        assert!(response.contains(
+            "android_tablet_bridge_pen_state{android_tablet_bridge_pen_state=\"hovering\"} 1\n"
+        ));
+        assert!(response.contains("android_tablet_bridge_rotation_degrees 90\n"));
+        assert!(response.ends_with("# EOF\n"));
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    cell::OnceCell,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use input_linux::{
+    sys::{input_event, uinput_abs_setup},
+    AbsoluteAxis, EventKind, InputEvent, InputId, InputProperty, Key, RelativeAxis, UInputHandle,
+};
+
+use crate::labels::event_labels;
+
+pub type SharedWriter = Arc<Mutex<Box<dyn Write + Send>>>;
+
+// A uinput device, or with --dry-run a device printing the events it would receive. The methods
+// mirror the UInputHandle ones so both are set up the same way.
+pub enum OutputDevice {
+    Uinput(UInputHandle<File>),
+    DryRun {
+        name: OnceCell<String>,
+        output: SharedWriter,
+    },
+}
+
+// "-" is stdout
+pub fn open_dry_run_output(path: &Path) -> io::Result<SharedWriter> {
+    let output: Box<dyn Write + Send> = match path.to_str() {
+        Some("-") => Box::new(io::stdout()),
+        _ => Box::new(BufWriter::new(File::create(path)?)),
+    };
+    Ok(Arc::new(Mutex::new(output)))
+}
+
+impl OutputDevice {
+    pub fn dry_run(output: SharedWriter) -> Self {
+        OutputDevice::DryRun {
+            name: OnceCell::new(),
+            output,
+        }
+    }
+
+    // Where actions with side effects outside of the device are printed instead of performed
+    pub fn dry_run_output(&self) -> Option<&SharedWriter> {
+        match self {
+            OutputDevice::Uinput(_) => None,
+            OutputDevice::DryRun { output, .. } => Some(output),
+        }
+    }
+
+    fn uinput(&self) -> Option<&UInputHandle<File>> {
+        match self {
+            OutputDevice::Uinput(uhandle) => Some(uhandle),
+            OutputDevice::DryRun { .. } => None,
+        }
+    }
+
+    pub fn set_evbit(&self, kind: EventKind) -> io::Result<()> {
+        self.uinput().map_or(Ok(()), |x| x.set_evbit(kind))
+    }
+
+    pub fn set_keybit(&self, key: Key) -> io::Result<()> {
+        self.uinput().map_or(Ok(()), |x| x.set_keybit(key))
+    }
+
+    pub fn set_relbit(&self, axis: RelativeAxis) -> io::Result<()> {
+        self.uinput().map_or(Ok(()), |x| x.set_relbit(axis))
+    }
+
+    pub fn set_absbit(&self, axis: AbsoluteAxis) -> io::Result<()> {
+        self.uinput().map_or(Ok(()), |x| x.set_absbit(axis))
+    }
+
+    pub fn set_propbit(&self, property: InputProperty) -> io::Result<()> {
+        self.uinput().map_or(Ok(()), |x| x.set_propbit(property))
+    }
+
+    pub fn abs_setup(&self, setup: &uinput_abs_setup) -> io::Result<()> {
+        self.uinput().map_or(Ok(()), |x| x.abs_setup(setup))
+    }
+
+    pub fn create(&self, id: &InputId, name: &[u8]) -> io::Result<()> {
+        match self {
+            OutputDevice::Uinput(uhandle) => uhandle.create(id, name, 0, &[]),
+            OutputDevice::DryRun {
+                name: device_name,
+                output,
+            } => {
+                let device_name = device_name.get_or_init(|| String::from_utf8_lossy(name).into());
+                let mut output = output.lock().unwrap_or_else(|err| err.into_inner());
+                writeln!(
+                    output,
+                    "add device: {} ({:04x}:{:04x}:{:04x})",
+                    device_name, id.bustype, id.vendor, id.product
+                )
+            }
+        }
+    }
+
+    pub fn write(&self, events: &[input_event]) -> io::Result<usize> {
+        match self {
+            OutputDevice::Uinput(uhandle) => uhandle.write(events),
+            OutputDevice::DryRun { name, output } => {
+                let mut output = output.lock().unwrap_or_else(|err| err.into_inner());
+                for event in events.iter().filter_map(|x| InputEvent::from_raw(x).ok()) {
+                    let (kind, code) = event_labels(event);
+                    writeln!(
+                        output,
+                        "[{:>8}.{:06}] {}: {:<8} {:<24} {}",
+                        event.time.seconds(),
+                        event.time.microseconds(),
+                        name.get().map_or("", String::as_str),
+                        kind,
+                        code,
+                        event.value
+                    )?;
+                }
+                Ok(events.len())
+            }
+        }
+    }
+
+    pub fn dev_destroy(&self) -> io::Result<()> {
+        match self {
+            OutputDevice::Uinput(uhandle) => uhandle.dev_destroy(),
+            OutputDevice::DryRun { output, .. } => {
+                output.lock().unwrap_or_else(|err| err.into_inner()).flush()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gesture::{perform_gesture_action, GestureAction};
+    use input_linux::{AbsoluteEvent, EventTime, KeyEvent, KeyState, SynchronizeEvent};
+    use pretty_assertions::assert_eq;
+
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn it_prints_dry_run_events() {
+        let captured = Arc::new(Mutex::new(vec![]));
+        let device =
+            OutputDevice::dry_run(Arc::new(Mutex::new(Box::new(Capture(captured.clone())))));
+        let time = EventTime::new(1338055, 981824);
+
+        device.set_evbit(EventKind::Key).unwrap();
+        device
+            .create(
+                &InputId {
+                    bustype: input_linux::sys::BUS_USB,
+                    vendor: 0x1234,
+                    product: 0x5678,
+                    version: 0,
+                },
+                b"Android Tablet Bridge",
+            )
+            .unwrap();
+        device
+            .write(&[
+                KeyEvent::new(time, Key::ButtonToolPen, KeyState::PRESSED)
+                    .into_event()
+                    .into_raw(),
+                AbsoluteEvent::new(time, AbsoluteAxis::X, 11370)
+                    .into_event()
+                    .into_raw(),
+                SynchronizeEvent::report(time).into_event().into_raw(),
+            ])
+            .unwrap();
+        perform_gesture_action(
+            &device,
+            &GestureAction::Command(String::from("touch /tmp/atb-dry-run")),
+            time,
+        )
+        .unwrap();
+        device.dev_destroy().unwrap();
+
+        assert_eq!(
+            String::from_utf8(captured.lock().unwrap().clone()).unwrap(),
+            "add device: Android Tablet Bridge (0003:1234:5678)\n\
+             [ 1338055.981824] Android Tablet Bridge: EV_KEY   BTN_TOOL_PEN             1\n\
+             [ 1338055.981824] Android Tablet Bridge: EV_ABS   ABS_X                    11370\n\
+             [ 1338055.981824] Android Tablet Bridge: EV_SYN   SYN_REPORT               0\n\
+             run command: touch /tmp/atb-dry-run\n"
+        );
+        assert!(!Path::new("/tmp/atb-dry-run").exists());
+    }
+}
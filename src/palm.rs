@@ -0,0 +1,268 @@
+use input_linux::{
+    AbsoluteAxis, AbsoluteEvent, Event, EventTime, Key, KeyEvent, KeyState, SynchronizeEvent,
+    SynchronizeKind,
+};
+
+#[derive(Clone, Copy, Default)]
+struct Contact {
+    active: bool,
+    rejected: bool,
+    forwarded: bool,
+    touch_major: i32,
+}
+
+pub struct PalmRejection {
+    grace_us: i64,
+    max_touch_major: Option<i32>,
+    pen_in_range: bool,
+    pen_left_at: Option<i64>,
+    slot: usize,
+    contacts: Vec<Contact>,
+    frame: Vec<(Option<usize>, Event)>,
+}
+
+impl PalmRejection {
+    pub fn new(grace_ms: u64, max_touch_major: Option<i32>) -> Self {
+        PalmRejection {
+            grace_us: grace_ms as i64 * 1000,
+            max_touch_major,
+            pen_in_range: false,
+            pen_left_at: None,
+            slot: 0,
+            contacts: vec![Contact::default()],
+            frame: vec![],
+        }
+    }
+
+    pub fn set_pen_in_range(&mut self, in_range: bool, time: EventTime) {
+        if self.pen_in_range && !in_range {
+            self.pen_left_at = Some(event_time_us(&time));
+        }
+        self.pen_in_range = in_range;
+    }
+
+    // Buffers touch events and returns the filtered frame once SYN_REPORT is received.
+    pub fn process(&mut self, event: Event) -> Vec<Event> {
+        match event {
+            Event::Absolute(absolute_event)
+                if absolute_event.axis == AbsoluteAxis::MultitouchSlot =>
+            {
+                self.slot = absolute_event.value.max(0) as usize;
+                if self.contacts.len() <= self.slot {
+                    self.contacts.resize(self.slot + 1, Contact::default());
+                }
+                vec![]
+            }
+            Event::Absolute(absolute_event) if is_multitouch_axis(absolute_event.axis) => {
+                let contact = &mut self.contacts[self.slot];
+                match absolute_event.axis {
+                    AbsoluteAxis::MultitouchTrackingId => {
+                        if absolute_event.value >= 0 && !contact.active {
+                            contact.rejected = false;
+                        }
+                        contact.active = absolute_event.value >= 0;
+                    }
+                    AbsoluteAxis::MultitouchTouchMajor => {
+                        contact.touch_major = absolute_event.value
+                    }
+                    _ => {}
+                }
+                self.frame.push((Some(self.slot), event));
+                vec![]
+            }
+            Event::Key(key_event) if key_event.key == Key::ButtonTouch => vec![],
+            Event::Synchronize(sync_event) if sync_event.kind == SynchronizeKind::Report => {
+                self.flush(sync_event.time)
+            }
+            _ => {
+                self.frame.push((None, event));
+                vec![]
+            }
+        }
+    }
+
+    fn flush(&mut self, time: EventTime) -> Vec<Event> {
+        let now = event_time_us(&time);
+        let pen_blocking = self.pen_in_range
+            || self
+                .pen_left_at
+                .is_some_and(|left_at| now - left_at < self.grace_us);
+
+        let forwarded_before = self.contacts.iter().filter(|x| x.forwarded).count();
+        let frame = std::mem::take(&mut self.frame);
+        let mut output = vec![];
+
+        for (index, contact) in self.contacts.iter_mut().enumerate() {
+            if contact.active
+                && !contact.rejected
+                && (pen_blocking
+                    || self
+                        .max_touch_major
+                        .is_some_and(|maximum| contact.touch_major > maximum))
+            {
+                contact.rejected = true;
+            }
+
+            let slot_events = frame
+                .iter()
+                .filter(|(slot, _)| *slot == Some(index))
+                .map(|(_, event)| *event);
+            let slot_event = Event::Absolute(AbsoluteEvent::new(
+                time,
+                AbsoluteAxis::MultitouchSlot,
+                index as i32,
+            ));
+
+            if contact.forwarded && contact.rejected {
+                output.push(slot_event);
+                output.push(Event::Absolute(AbsoluteEvent::new(
+                    time,
+                    AbsoluteAxis::MultitouchTrackingId,
+                    -1,
+                )));
+                contact.forwarded = false;
+            } else if contact.forwarded || (contact.active && !contact.rejected) {
+                let mut slot_events = slot_events.peekable();
+                if slot_events.peek().is_some() {
+                    output.push(slot_event);
+                    output.extend(slot_events);
+                }
+                contact.forwarded = contact.active;
+            }
+
+            if !contact.active {
+                contact.rejected = false;
+            }
+        }
+
+        let forwarded_after = self.contacts.iter().filter(|x| x.forwarded).count();
+
+        for (slot, event) in frame {
+            let is_release =
+                matches!(event, Event::Key(key_event) if !key_event.value.is_pressed());
+            if slot.is_none() && (forwarded_after > 0 || is_release) {
+                output.push(event);
+            }
+        }
+
+        if (forwarded_before == 0) != (forwarded_after == 0) {
+            output.push(Event::Key(KeyEvent::new(
+                time,
+                Key::ButtonTouch,
+                KeyState::pressed(forwarded_after > 0),
+            )));
+        }
+
+        if !output.is_empty() {
+            output.push(Event::Synchronize(SynchronizeEvent::report(time)));
+        }
+
+        output
+    }
+}
+
+fn is_multitouch_axis(axis: AbsoluteAxis) -> bool {
+    (AbsoluteAxis::MultitouchSlot as u16..=AbsoluteAxis::MultitouchToolY as u16)
+        .contains(&(axis as u16))
+}
+
+fn event_time_us(time: &EventTime) -> i64 {
+    time.seconds() * 1_000_000 + time.microseconds()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn time(ms: i64) -> EventTime {
+        EventTime::new(ms / 1000, (ms % 1000) * 1000)
+    }
+
+    fn abs(ms: i64, axis: AbsoluteAxis, value: i32) -> Event {
+        Event::Absolute(AbsoluteEvent::new(time(ms), axis, value))
+    }
+
+    fn touch_down(palm: &mut PalmRejection, ms: i64, major: i32) -> Vec<Event> {
+        let mut output = vec![];
+        for event in [
+            abs(ms, AbsoluteAxis::MultitouchSlot, 0),
+            abs(ms, AbsoluteAxis::MultitouchTrackingId, 12),
+            abs(ms, AbsoluteAxis::MultitouchPositionX, 100),
+            abs(ms, AbsoluteAxis::MultitouchTouchMajor, major),
+            Event::Key(KeyEvent::new(time(ms), Key::ButtonTouch, KeyState::PRESSED)),
+            Event::Synchronize(SynchronizeEvent::report(time(ms))),
+        ] {
+            output.extend(palm.process(event));
+        }
+        output
+    }
+
+    fn pen(palm: &mut PalmRejection, ms: i64, in_range: bool) {
+        palm.set_pen_in_range(in_range, time(ms));
+    }
+
+    #[test]
+    fn it_forwards_touch_without_pen() {
+        let mut palm = PalmRejection::new(500, None);
+
+        assert_eq!(
+            touch_down(&mut palm, 0, 10),
+            vec![
+                abs(0, AbsoluteAxis::MultitouchSlot, 0),
+                abs(0, AbsoluteAxis::MultitouchTrackingId, 12),
+                abs(0, AbsoluteAxis::MultitouchPositionX, 100),
+                abs(0, AbsoluteAxis::MultitouchTouchMajor, 10),
+                Event::Key(KeyEvent::new(time(0), Key::ButtonTouch, KeyState::PRESSED)),
+                Event::Synchronize(SynchronizeEvent::report(time(0))),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_rejects_touch_during_pen_grace_period() {
+        let mut palm = PalmRejection::new(500, None);
+
+        pen(&mut palm, 0, true);
+        assert_eq!(touch_down(&mut palm, 100, 10), vec![]);
+
+        pen(&mut palm, 200, false);
+        palm.process(abs(300, AbsoluteAxis::MultitouchTrackingId, -1));
+        assert_eq!(
+            palm.process(Event::Synchronize(SynchronizeEvent::report(time(300)))),
+            vec![]
+        );
+
+        assert_eq!(touch_down(&mut palm, 600, 10), vec![]);
+    }
+
+    #[test]
+    fn it_cancels_forwarded_contact_when_pen_arrives() {
+        let mut palm = PalmRejection::new(0, None);
+
+        touch_down(&mut palm, 0, 10);
+        pen(&mut palm, 50, true);
+
+        palm.process(abs(60, AbsoluteAxis::MultitouchPositionX, 120));
+        assert_eq!(
+            palm.process(Event::Synchronize(SynchronizeEvent::report(time(60)))),
+            vec![
+                abs(60, AbsoluteAxis::MultitouchSlot, 0),
+                abs(60, AbsoluteAxis::MultitouchTrackingId, -1),
+                Event::Key(KeyEvent::new(
+                    time(60),
+                    Key::ButtonTouch,
+                    KeyState::RELEASED
+                )),
+                Event::Synchronize(SynchronizeEvent::report(time(60))),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_rejects_large_contacts() {
+        let mut palm = PalmRejection::new(500, Some(40));
+
+        assert_eq!(touch_down(&mut palm, 0, 80), vec![]);
+    }
+}
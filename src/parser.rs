@@ -1,34 +1,61 @@
 use core::str;
 
 use nom::{
-    bytes::complete::{tag, take, take_until, take_while_m_n},
+    branch::alt,
+    bytes::complete::{tag, take_till1, take_until, take_while, take_while1, take_while_m_n},
     character::{
         self,
         complete::{
-            alpha1, alphanumeric1, digit1, i32, multispace0, multispace1, newline, not_line_ending,
-            space0, space1,
+            alphanumeric1, digit1, i32, multispace0, multispace1, newline, not_line_ending, space0,
+            space1,
         },
         streaming::char,
     },
-    combinator::{map_res, opt},
-    multi::many1,
-    sequence::separated_pair,
+    combinator::{all_consuming, eof, map_opt, map_res, opt, value},
+    multi::{many0, many1},
+    sequence::{delimited, preceded, separated_pair, terminated},
     IResult, Parser,
 };
 
 use input_linux::{
-    AbsoluteAxis, AbsoluteEvent, AbsoluteInfo, AbsoluteInfoSetup, Event, EventKind, EventTime, Key,
-    KeyEvent, KeyState, RelativeAxis, SwitchKind,
+    AbsoluteAxis, AbsoluteEvent, AbsoluteInfo, AbsoluteInfoSetup, Event, EventKind, EventTime,
+    InputProperty, Key, KeyEvent, KeyState, RelativeAxis, SwitchKind, SynchronizeEvent,
+    SynchronizeKind,
 };
 
-#[derive(Debug, PartialEq)]
+use crate::{
+    evtest::{is_evtest_description, parse_evtest_devices_lenient},
+    labels::{self, code},
+};
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct ADBDevice {
     pub path: String,
     pub name: String,
+    pub id: Option<ADBDeviceId>,
     pub events: ADBDeviceEvents,
+    pub properties: Vec<InputProperty>,
 }
 
+// Reported by getevent -i
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ADBDeviceId {
+    pub bustype: u16,
+    pub vendor: u16,
+    pub product: u16,
+    pub version: u16,
+    pub location: String,
+    pub unique_id: String,
+}
+
+// A device block of getevent output which could not be parsed, starting with its header line.
 #[derive(Debug, PartialEq)]
+pub struct SkippedDevice {
+    pub header: String,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct ADBDeviceEvents {
     pub keys: Vec<Key>,
     pub relative: Vec<RelativeAxis>,
@@ -36,6 +63,17 @@ pub struct ADBDeviceEvents {
     pub switches: Vec<(SwitchKind, bool)>,
 }
 
+enum DeviceHeader<'a> {
+    Name(&'a str),
+    Bus(u16),
+    Vendor(u16),
+    Product(u16),
+    Version(u16),
+    Location(&'a str),
+    UniqueId(&'a str),
+    DriverVersion,
+}
+
 enum DeviceEvent {
     Keys(Vec<Key>),
     Relative(Vec<RelativeAxis>),
@@ -45,17 +83,43 @@ enum DeviceEvent {
 
 pub fn parse_input_event(input: &str) -> IResult<&str, Option<Event>> {
     let (input, time) = parse_event_time(input)?;
-    let (input, _) = char(' ')(input)?;
+    let (input, _) = space1(input)?;
+    // Present when getevent reads several devices at once
+    let (input, _) = opt(terminated(
+        preceded(char('/'), take_till1(|c| c == ':')),
+        tag(": "),
+    ))(input)?;
     let (input, event_kind) = parse_event_kind(input)?;
-    let (input, _) = char(' ')(input)?;
+    let (input, _) = space1(input)?;
 
     match event_kind {
+        EventKind::Synchronize => {
+            let (input, kind) =
+                map_res(parse_code(labels::SYNCHRONIZE), SynchronizeKind::from_code)(input)?;
+            let (input, _) = space1(input)?;
+            let (input, hex_value) = take_while_m_n(8, 8, is_hex_digit)(input)?;
+            let value = u32::from_str_radix(hex_value, 16).unwrap();
+
+            Ok((
+                input,
+                Some(Event::Synchronize(SynchronizeEvent::new(
+                    time,
+                    kind,
+                    value as i32,
+                ))),
+            ))
+        }
         EventKind::Key => {
             let (input, key) = parse_device_event_key(input)?;
-            let (input, _) = char(' ')(input)?;
-            let (input, value) = map_res(take_while_m_n(8, 8, is_hex_digit), |input| {
-                i32::from_str_radix(input, 16)
-            })
+            let (input, _) = space1(input)?;
+            let (input, value) = alt((
+                value(0, tag("UP")),
+                value(1, tag("DOWN")),
+                value(2, tag("REPEAT")),
+                map_res(take_while_m_n(8, 8, is_hex_digit), |input| {
+                    i32::from_str_radix(input, 16)
+                }),
+            ))
             .parse(input)?;
             Ok((
                 input,
@@ -65,7 +129,7 @@ pub fn parse_input_event(input: &str) -> IResult<&str, Option<Event>> {
         EventKind::Absolute => {
             let (input, axis) = parse_absolute_axis(input)?;
 
-            let (input, _) = char(' ')(input)?;
+            let (input, _) = space1(input)?;
 
             let (input, hex_value) = take_while_m_n(8, 8, is_hex_digit)(input)?;
             let value = u32::from_str_radix(hex_value, 16).unwrap();
@@ -83,12 +147,93 @@ pub fn parse_input_event(input: &str) -> IResult<&str, Option<Event>> {
     }
 }
 
-pub fn parse_devices(input: &str) -> IResult<&str, Vec<ADBDevice>> {
-    many1(parse_device)(input)
+pub fn parse_devices(input: &str) -> Result<Vec<ADBDevice>, String> {
+    let (devices, skipped) = parse_devices_lenient(input);
+
+    match skipped.first() {
+        Some(skipped) => Err(format!("{}: {}", skipped.header, skipped.reason)),
+        None if devices.is_empty() => Err(String::from("no device found")),
+        None => Ok(devices),
+    }
+}
+
+// Parses each "add device" block on its own so one unexpected block does not hide the others,
+// lines outside of blocks like "could not get driver version for ..." are ignored.
+pub fn parse_devices_lenient(input: &str) -> (Vec<ADBDevice>, Vec<SkippedDevice>) {
+    let input = input.replace("\r\n", "\n");
+    if is_evtest_description(&input) {
+        return parse_evtest_devices_lenient(&input);
+    }
+
+    let mut blocks: Vec<String> = vec![];
+    let mut in_block = false;
+
+    for line in input.split_inclusive('\n') {
+        if line.starts_with("add device ") {
+            blocks.push(line.to_string());
+            in_block = true;
+        } else if in_block && line.starts_with(char::is_whitespace) {
+            if let Some(block) = blocks.last_mut() {
+                block.push_str(line);
+            }
+        } else {
+            in_block = false;
+        }
+    }
+
+    parse_device_blocks(blocks, parse_device)
+}
+
+// Each block is parsed on its own, a block which cannot be parsed is skipped with the line where
+// parsing stopped.
+pub fn parse_device_blocks(
+    blocks: Vec<String>,
+    parser: fn(&str) -> IResult<&str, ADBDevice>,
+) -> (Vec<ADBDevice>, Vec<SkippedDevice>) {
+    let mut devices = vec![];
+    let mut skipped = vec![];
+
+    for block in blocks {
+        match all_consuming(terminated(parser, multispace0))(&block) {
+            Ok((_, device)) => devices.push(device),
+            Err(err) => {
+                let rest = match err {
+                    nom::Err::Error(ref err) | nom::Err::Failure(ref err) => err.input,
+                    nom::Err::Incomplete(_) => "",
+                };
+                let line = rest.trim_start().lines().next().unwrap_or_default();
+                skipped.push(SkippedDevice {
+                    header: block.lines().next().unwrap_or_default().to_string(),
+                    reason: if line.is_empty() {
+                        String::from("unexpected end of device")
+                    } else {
+                        format!("unexpected line {:?}", line.trim())
+                    },
+                });
+            }
+        }
+    }
+
+    (devices, skipped)
 }
 
 fn parse_event_kind(input: &str) -> IResult<&str, EventKind> {
-    map_res(map_res(take(4usize), from_hex), EventKind::from_type)(input)
+    map_res(parse_code(labels::EVENT_KINDS), EventKind::from_type)(input)
+}
+
+// getevent prints codes as 4 hex digits, or as their names with -l when it knows them.
+fn parse_code<'a>(
+    table: &'static [(&'static str, u16)],
+) -> impl FnMut(&'a str) -> IResult<&'a str, u16> {
+    move |input| {
+        alt((
+            map_res(take_while_m_n(4, 4, is_hex_digit), from_hex),
+            map_opt(
+                take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_'),
+                |label| code(table, label),
+            ),
+        ))(input)
+    }
 }
 
 fn parse_event_time(input: &str) -> IResult<&str, EventTime> {
@@ -102,20 +247,98 @@ fn parse_event_time(input: &str) -> IResult<&str, EventTime> {
 
 fn parse_device(input: &str) -> IResult<&str, ADBDevice> {
     let (input, path) = parse_device_path(input)?;
-    let (input, name) = parse_device_name(input)?;
-    let (input, events) = parse_device_events(input)?;
-    let (input, _) = parse_device_input_properties(input)?;
+    let (input, headers) = many0(parse_device_header)(input)?;
+    let (input, events) = opt(parse_device_events)(input)?;
+    let (input, properties) = opt(parse_device_input_properties)(input)?;
+    let (input, _) = match properties {
+        Some(_) => opt(parse_device_hid_descriptor)(input)?,
+        None => (input, None),
+    };
+
+    let mut name = "";
+    let mut id = ADBDeviceId::default();
+    let mut has_id = false;
+
+    for header in headers {
+        has_id |= matches!(header, DeviceHeader::Bus(_));
+        match header {
+            DeviceHeader::Name(value) => name = value,
+            DeviceHeader::Bus(value) => id.bustype = value,
+            DeviceHeader::Vendor(value) => id.vendor = value,
+            DeviceHeader::Product(value) => id.product = value,
+            DeviceHeader::Version(value) => id.version = value,
+            DeviceHeader::Location(value) => id.location = value.to_owned(),
+            DeviceHeader::UniqueId(value) => id.unique_id = value.to_owned(),
+            DeviceHeader::DriverVersion => {}
+        }
+    }
 
     Ok((
         input,
         ADBDevice {
             path: path.to_owned(),
             name: name.to_owned(),
-            events,
+            id: has_id.then_some(id),
+            events: events.unwrap_or_else(|| ADBDeviceEvents {
+                keys: vec![],
+                relative: vec![],
+                absolute: vec![],
+                switches: vec![],
+            }),
+            properties: properties.unwrap_or_default(),
         },
     ))
 }
 
+// The name, and with -i the input id, location, unique id and driver version. The driver version
+// line is "version:" while the input id version has no colon.
+fn parse_device_header(input: &str) -> IResult<&str, DeviceHeader<'_>> {
+    let (input, _) = space1(input)?;
+    alt((
+        parse_device_name.map(DeviceHeader::Name),
+        terminated(parse_device_id_field("bus:"), newline).map(DeviceHeader::Bus),
+        terminated(parse_device_id_field("vendor"), newline).map(DeviceHeader::Vendor),
+        terminated(parse_device_id_field("product"), newline).map(DeviceHeader::Product),
+        terminated(parse_device_id_field("version"), newline).map(DeviceHeader::Version),
+        parse_device_quoted("location:").map(DeviceHeader::Location),
+        parse_device_quoted("id:").map(DeviceHeader::UniqueId),
+        terminated(preceded(tag("version:"), not_line_ending), newline)
+            .map(|_| DeviceHeader::DriverVersion),
+    ))(input)
+}
+
+// The HID descriptor hex dump printed by recent getevent -i versions after the input properties
+fn parse_device_hid_descriptor(input: &str) -> IResult<&str, ()> {
+    let (input, _) = delimited(space1, tag("HID descriptor:"), not_line_ending)(input)?;
+    let (input, _) = newline(input)?;
+    let (input, _) = many0(terminated(
+        take_while(|c: char| c.is_ascii_hexdigit() || c == ' ' || c == '\t'),
+        newline,
+    ))(input)?;
+    Ok((input, ()))
+}
+
+fn parse_device_id_field<'a>(label: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, u16> {
+    preceded(
+        terminated(tag(label), space1),
+        map_res(take_while_m_n(4, 4, is_hex_digit), from_hex),
+    )
+}
+
+fn parse_device_quoted<'a>(
+    label: &'static str,
+) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    move |input| {
+        let (input, _) = tag(label)(input)?;
+        let (input, _) = space1(input)?;
+        let (input, _) = char('"')(input)?;
+        let (input, value) = take_until("\"")(input)?;
+        let (input, _) = char('"')(input)?;
+        let (input, _) = newline(input)?;
+        Ok((input, value))
+    }
+}
+
 fn parse_device_path(input: &str) -> IResult<&str, &str> {
     let (input, _) = tag("add device ")(input)?;
     let (input, _) = digit1(input)?;
@@ -127,7 +350,6 @@ fn parse_device_path(input: &str) -> IResult<&str, &str> {
 }
 
 fn parse_device_name(input: &str) -> IResult<&str, &str> {
-    let (input, _) = space1(input)?;
     let (input, _) = tag("name:")(input)?;
     let (input, _) = space1(input)?;
     let (input, _) = char('"')(input)?;
@@ -138,10 +360,10 @@ fn parse_device_name(input: &str) -> IResult<&str, &str> {
 }
 
 fn parse_device_events(input: &str) -> IResult<&str, ADBDeviceEvents> {
-    let (input, _) = space1(input)?;
+    let (input, _) = multispace1(input)?;
     let (input, _) = tag("events:")(input)?;
     let (input, _) = newline(input)?;
-    let (input, events) = many1(parse_device_event)(input)?;
+    let (input, events) = many0(parse_device_event)(input)?;
 
     let mut device_events = ADBDeviceEvents {
         keys: vec![],
@@ -165,32 +387,31 @@ fn parse_device_events(input: &str) -> IResult<&str, ADBDeviceEvents> {
     Ok((input, device_events))
 }
 
-fn parse_device_input_properties(input: &str) -> IResult<&str, ()> {
+fn parse_device_input_properties(input: &str) -> IResult<&str, Vec<InputProperty>> {
     let (input, _) = multispace1(input)?;
     let (input, _) = tag("input props:")(input)?;
     let (input, _) = newline(input)?;
-    let (input, _) = multispace1(input)?;
+    let (input, _) = multispace0(input)?;
     let (input, res) = opt(tag("<none>"))(input)?;
     match res {
         Some(_) => {
-            let (input, _) = newline(input)?;
-            Ok((input, ()))
+            let (input, _) = alt((newline.map(|_| ""), eof))(input)?;
+            Ok((input, vec![]))
         }
         None => {
             // INPUT_PROP_DIRECT
-            let (input, _) = many1(parse_device_input_property)(input)?;
-            // let (input, _) = newline(input)?;
-            Ok((input, ()))
+            let (input, properties) = many0(parse_device_input_property)(input)?;
+            Ok((input, properties.into_iter().flatten().collect()))
         }
     }
 }
 
-fn parse_device_input_property(input: &str) -> IResult<&str, &str> {
+fn parse_device_input_property(input: &str) -> IResult<&str, Option<InputProperty>> {
     let (input, _) = multispace0(input)?;
-    let (input, _) = tag("INPUT_PROP_")(input)?;
-    let (input, prop) = alpha1(input)?;
-    let (input, _) = multispace1(input)?;
-    Ok((input, prop))
+    let (input, property) = parse_code(labels::PROPERTIES)(input)?;
+    let (input, _) = multispace0(input)?;
+
+    Ok((input, InputProperty::from_code(property).ok()))
 }
 
 fn parse_device_event(input: &str) -> IResult<&str, Option<DeviceEvent>> {
@@ -219,12 +440,25 @@ fn parse_device_event(input: &str) -> IResult<&str, Option<DeviceEvent>> {
             let (input, keys) = many1(parse_device_event_switch)(input)?;
             Ok((input, Some(DeviceEvent::Switch(keys))))
         }
-        _ => Ok((input, None)),
+        _ => {
+            // LED, MSC, REP, FF... are not forwarded
+            let (input, _) = many0(preceded(
+                multispace1,
+                alt((
+                    terminated(take_while_m_n(4, 4, is_hex_digit), opt(char('*'))),
+                    map_opt(
+                        take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_'),
+                        |label: &str| label.contains('_').then_some(label),
+                    ),
+                )),
+            ))(input)?;
+            Ok((input, None))
+        }
     }
 }
 
 fn parse_absolute_axis(input: &str) -> IResult<&str, AbsoluteAxis> {
-    map_res(map_res(take(4usize), from_hex), AbsoluteAxis::from_code)(input)
+    map_res(parse_code(labels::ABSOLUTE), AbsoluteAxis::from_code)(input)
 }
 
 fn parse_absolute_info_setup(input: &str) -> IResult<&str, AbsoluteInfoSetup> {
@@ -246,9 +480,9 @@ fn parse_absolute_info_setup(input: &str) -> IResult<&str, AbsoluteInfoSetup> {
     let (input, _) = char(',')(input)?;
     let (input, _) = tag(" flat ")(input)?;
     let (input, flat) = i32(input)?;
-    let (input, _) = char(',')(input)?;
-    let (input, _) = tag(" resolution ")(input)?;
-    let (input, resolution) = i32(input)?;
+    // Older Android versions do not print the resolution
+    let (input, resolution) = opt(preceded(tag(", resolution "), i32))(input)?;
+    let (input, _) = space0(input)?;
     let (input, _) = newline(input)?;
 
     Ok((
@@ -261,7 +495,7 @@ fn parse_absolute_info_setup(input: &str) -> IResult<&str, AbsoluteInfoSetup> {
                 maximum,
                 fuzz,
                 flat,
-                resolution,
+                resolution: resolution.unwrap_or_default(),
             },
         },
     ))
@@ -269,23 +503,27 @@ fn parse_absolute_info_setup(input: &str) -> IResult<&str, AbsoluteInfoSetup> {
 
 fn parse_device_event_key(input: &str) -> IResult<&str, Key> {
     let (input, _) = multispace0(input)?;
-    map_res(map_res(take(4usize), from_hex), Key::from_code)(input)
+    map_res(parse_code(labels::KEYS), Key::from_code)(input)
 }
 
 fn parse_device_event_relative(input: &str) -> IResult<&str, RelativeAxis> {
     let (input, _) = multispace1(input)?;
-    map_res(map_res(take(4usize), from_hex), RelativeAxis::from_code)(input)
+    map_res(parse_code(labels::RELATIVE), RelativeAxis::from_code)(input)
 }
 
 fn parse_device_event_switch(input: &str) -> IResult<&str, (SwitchKind, bool)> {
     let (input, _) = multispace1(input)?;
-    let (input, switch) = map_res(map_res(take(4usize), from_hex), SwitchKind::from_code)(input)?;
+    let (input, switch) = map_res(parse_code(labels::SWITCHES), SwitchKind::from_code)(input)?;
     let (input, switch_value) = opt(char('*'))(input)?;
     Ok((input, (switch, switch_value.is_some())))
 }
 
+pub fn parse_key_name(input: &str) -> Option<Key> {
+    Key::iter().find(|key| format!("{:?}", key).eq_ignore_ascii_case(input))
+}
+
 fn is_hex_digit(c: char) -> bool {
-    c.is_digit(16)
+    c.is_ascii_hexdigit()
 }
 
 fn from_hex(input: &str) -> Result<u16, std::num::ParseIntError> {
@@ -306,26 +544,63 @@ mod tests {
                 panic!("Parsing events should succeed, got error: {}", err)
             }
         }
+
+        // Labelled streams from getevent -l, with the path when several subdevices are read
+        for (line, expected) in [
+            (
+                "[   12001.309814] EV_KEY       BTN_TOOL_PEN         DOWN",
+                Event::Key(KeyEvent::new(
+                    EventTime::new(12001, 309814),
+                    Key::ButtonToolPen,
+                    KeyState::PRESSED,
+                )),
+            ),
+            (
+                "[   12001.309814] /dev/input/event9: EV_ABS       ABS_TILT_X           fffffff5",
+                Event::Absolute(AbsoluteEvent::new(
+                    EventTime::new(12001, 309814),
+                    AbsoluteAxis::TiltX,
+                    -11,
+                )),
+            ),
+            (
+                "[   12001.309814] EV_SYN       SYN_REPORT           00000000",
+                Event::Synchronize(SynchronizeEvent::report(EventTime::new(12001, 309814))),
+            ),
+        ] {
+            assert_eq!(parse_input_event(line).unwrap().1, Some(expected));
+        }
     }
 
     #[test]
     fn it_parses_devices() {
         let data = include_str!("../devices.txt");
 
+        let (input, _) = many1(parse_device)(data).unwrap();
+        assert_eq!(
+            input, "",
+            "There should be no input left when parsing devices"
+        );
+
         match parse_devices(data) {
             Err(err) => {
                 panic!("Parsing devices should succeed, got error: {}", err)
             }
-            Ok((input, devices)) => {
-                assert_eq!(
-                    input, "",
-                    "There should be no input left when parsing devices"
-                );
+            Ok(devices) => {
                 assert_eq!(
                     devices.len(),
                     13,
                     "There should have been 13 devices parsed"
                 );
+                assert_eq!(
+                    devices
+                        .iter()
+                        .find(|x| x.name == "sec_touchscreen")
+                        .map(|x| x.properties.clone()),
+                    Some(vec![InputProperty::Direct]),
+                    "Touchscreen should have the direct input property"
+                );
+
                 let device_opt = devices.iter().find(|x| x.name == "sec_e-pen");
 
                 match device_opt {
@@ -336,6 +611,7 @@ mod tests {
                             &ADBDevice {
                                 path: String::from("/dev/input/event9"),
                                 name: "sec_e-pen".to_owned(),
+                                id: None,
                                 events: ADBDeviceEvents {
                                     relative: vec![],
                                     keys: vec![
@@ -419,7 +695,8 @@ mod tests {
                                         (SwitchKind::PenInserted, true),
                                         (SwitchKind::MachineCover, false)
                                     ]
-                                }
+                                },
+                                properties: vec![],
                             }
                         )
                     }
@@ -427,4 +704,75 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn it_parses_labelled_devices_leniently() {
+        let data = include_str!("../devices-labelled.txt");
+        let (devices, _) = parse_devices_lenient(include_str!("../devices.txt"));
+        let pen = devices.into_iter().find(|x| x.name == "sec_e-pen").unwrap();
+
+        assert_eq!(
+            parse_devices(data),
+            Err(String::from(
+                "add device 4: /dev/input/event4: unexpected line \"ABS_MT_POSITION_Y     : value 0, min 0, max ???, fuzz 0, flat 0, resolution 0\""
+            ))
+        );
+        assert!(parse_devices("/system/bin/sh: getevent: not found\n").is_err());
+
+        let (devices, skipped) = parse_devices_lenient(&data.replace('\n', "\r\n"));
+        assert_eq!(
+            skipped
+                .iter()
+                .map(|x| x.header.as_str())
+                .collect::<Vec<_>>(),
+            vec!["add device 4: /dev/input/event4"]
+        );
+        assert_eq!(
+            devices.iter().map(|x| x.name.as_str()).collect::<Vec<_>>(),
+            vec!["sec_e-pen", "gpio_keys", "virtual_sensor", "legacy_touch"]
+        );
+        assert_eq!(
+            devices[0],
+            ADBDevice {
+                id: Some(ADBDeviceId::default()),
+                ..pen
+            }
+        );
+        assert_eq!(
+            devices[1].id,
+            Some(ADBDeviceId {
+                bustype: 0x19,
+                vendor: 1,
+                product: 1,
+                version: 0x100,
+                location: String::from("gpio-keys/input0"),
+                unique_id: String::new(),
+            })
+        );
+        assert_eq!(devices[3].id, None);
+        assert_eq!(
+            devices[1].events.keys,
+            vec![Key::VolumeDown, Key::VolumeUp, Key::Power]
+        );
+        assert_eq!(devices[2].events.absolute, vec![]);
+        assert_eq!(devices[3].events.absolute[1].info.maximum, 1279);
+
+        let (devices, skipped) = parse_devices_lenient(
+            "add device 1: /dev/input/event3\n  \
+               nmae:     \"typo\"\n  \
+               events:\n\
+             add device 2: /dev/input/event2\n  \
+               name:     \"hid_keys\"\n  \
+               input props:\n    \
+                 <none>\n  \
+               HID descriptor: 0003:18d1:4ee7\n\n    \
+                 05 01 09 06 a1 01\n    \
+                 c0\n\n",
+        );
+        assert_eq!(
+            skipped[0].reason,
+            String::from("unexpected line \"nmae:     \\\"typo\\\"\"")
+        );
+        assert_eq!(devices[0].name, "hid_keys");
+    }
 }
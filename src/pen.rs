@@ -0,0 +1,305 @@
+use input_linux::{
+    AbsoluteAxis, Event, EventTime, Key, KeyEvent, KeyState, SynchronizeEvent, SynchronizeKind,
+};
+
+const TOOLS: [Key; 7] = [
+    Key::ButtonToolPen,
+    Key::ButtonToolRubber,
+    Key::ButtonToolBrush,
+    Key::ButtonToolPencil,
+    Key::ButtonToolAirbrush,
+    Key::ButtonToolMouse,
+    Key::ButtonToolLens,
+];
+
+const BUTTONS: [Key; 3] = [Key::ButtonStylus, Key::ButtonStylus2, Key::ButtonStylus3];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PenPhase {
+    OutOfRange,
+    Hovering,
+    Touching,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PenState {
+    pub tool: Option<Key>,
+    pub touching: bool,
+    pub buttons: Vec<Key>,
+}
+
+impl PenState {
+    pub fn phase(&self) -> PenPhase {
+        match (self.tool, self.touching) {
+            (None, _) => PenPhase::OutOfRange,
+            (Some(_), false) => PenPhase::Hovering,
+            (Some(_), true) => PenPhase::Touching,
+        }
+    }
+
+    pub fn in_range(&self) -> bool {
+        self.tool.is_some()
+    }
+}
+
+// Keeps the host view of the pen consistent: redundant or out of order tool and button events
+// are fixed up, and everything held is released when the stream can no longer be trusted.
+#[derive(Default)]
+pub struct PenTracker {
+    state: PenState,
+    time: Option<EventTime>,
+    dropping: bool,
+    resync_tool: Option<Key>,
+    resyncing: bool,
+}
+
+impl PenTracker {
+    pub fn new() -> Self {
+        PenTracker::default()
+    }
+
+    pub fn state(&self) -> &PenState {
+        &self.state
+    }
+
+    pub fn process(&mut self, event: Event) -> Vec<Event> {
+        let time = event.as_event().time;
+        self.time = Some(time);
+
+        match event {
+            Event::Synchronize(sync_event) if sync_event.kind == SynchronizeKind::Dropped => {
+                // Events are lost until the next SYN_REPORT, the tool is restored once the pen
+                // shows up again.
+                self.dropping = true;
+                let tool = self.state.tool;
+                let output = self.release();
+                self.resync_tool = tool;
+                output
+            }
+            Event::Synchronize(sync_event) if self.dropping => {
+                if sync_event.kind == SynchronizeKind::Report {
+                    self.dropping = false;
+                }
+                vec![]
+            }
+            _ if self.dropping => vec![],
+            Event::Key(key_event) if TOOLS.contains(&key_event.key) => {
+                let pressed = key_event.value.is_pressed();
+                self.resync_tool = None;
+
+                match (self.state.tool, pressed) {
+                    (Some(tool), true) if tool == key_event.key => vec![],
+                    (Some(_), true) => {
+                        let mut output = self.release_tool(time);
+                        self.state.tool = Some(key_event.key);
+                        output.push(event);
+                        output
+                    }
+                    (None, true) => {
+                        self.state.tool = Some(key_event.key);
+                        vec![event]
+                    }
+                    (Some(tool), false) if tool == key_event.key => self.release_tool(time),
+                    _ => vec![],
+                }
+            }
+            Event::Key(key_event) if key_event.key == Key::ButtonTouch => {
+                let pressed = key_event.value.is_pressed();
+                if pressed == self.state.touching {
+                    return vec![];
+                }
+                let mut output = self.resync(time);
+                self.state.touching = pressed;
+                output.push(event);
+                output
+            }
+            Event::Key(key_event) if BUTTONS.contains(&key_event.key) => {
+                let pressed = key_event.value.is_pressed();
+                if pressed == self.state.buttons.contains(&key_event.key) {
+                    return vec![];
+                }
+                if pressed {
+                    self.state.buttons.push(key_event.key);
+                } else {
+                    self.state.buttons.retain(|x| *x != key_event.key);
+                }
+                vec![event]
+            }
+            Event::Absolute(absolute_event) => {
+                let mut output = self.resync(time);
+                if absolute_event.axis == AbsoluteAxis::Pressure
+                    && absolute_event.value > 0
+                    && self.resyncing
+                    && !self.state.touching
+                {
+                    self.state.touching = true;
+                    output.push(key_event(time, Key::ButtonTouch, true));
+                }
+                output.push(event);
+                output
+            }
+            Event::Synchronize(sync_event) if sync_event.kind == SynchronizeKind::Report => {
+                self.resyncing = false;
+                vec![event]
+            }
+            _ => vec![event],
+        }
+    }
+
+    // Releases whatever the host believes is held, followed by a SYN_REPORT.
+    pub fn release(&mut self) -> Vec<Event> {
+        self.resync_tool = None;
+
+        let time = match self.time {
+            Some(time) => time,
+            None => return vec![],
+        };
+
+        let mut output = self.release_tool(time);
+        if !output.is_empty() {
+            output.push(Event::Synchronize(SynchronizeEvent::report(time)));
+        }
+        output
+    }
+
+    fn release_tool(&mut self, time: EventTime) -> Vec<Event> {
+        let mut output: Vec<Event> = std::mem::take(&mut self.state.buttons)
+            .into_iter()
+            .map(|button| key_event(time, button, false))
+            .collect();
+
+        if std::mem::take(&mut self.state.touching) {
+            output.push(key_event(time, Key::ButtonTouch, false));
+        }
+
+        if let Some(tool) = self.state.tool.take() {
+            output.push(key_event(time, tool, false));
+        }
+
+        output
+    }
+
+    fn resync(&mut self, time: EventTime) -> Vec<Event> {
+        match (self.state.tool, self.resync_tool.take()) {
+            (None, Some(tool)) => {
+                self.state.tool = Some(tool);
+                self.resyncing = true;
+                vec![key_event(time, tool, true)]
+            }
+            _ => vec![],
+        }
+    }
+}
+
+fn key_event(time: EventTime, key: Key, pressed: bool) -> Event {
+    Event::Key(KeyEvent::new(time, key, KeyState::pressed(pressed)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input_linux::AbsoluteEvent;
+    use pretty_assertions::assert_eq;
+
+    fn time() -> EventTime {
+        EventTime::new(1, 0)
+    }
+
+    fn key(key: Key, pressed: bool) -> Event {
+        key_event(time(), key, pressed)
+    }
+
+    fn absolute(axis: AbsoluteAxis, value: i32) -> Event {
+        Event::Absolute(AbsoluteEvent::new(time(), axis, value))
+    }
+
+    fn sync(kind: SynchronizeKind) -> Event {
+        Event::Synchronize(SynchronizeEvent::new(time(), kind, 0))
+    }
+
+    fn process(tracker: &mut PenTracker, events: Vec<Event>) -> Vec<Event> {
+        events
+            .into_iter()
+            .flat_map(|event| tracker.process(event))
+            .collect()
+    }
+
+    #[test]
+    fn it_tracks_pen_and_releases_held_state() {
+        let mut tracker = PenTracker::new();
+
+        let output = process(
+            &mut tracker,
+            vec![
+                key(Key::ButtonToolPen, true),
+                key(Key::ButtonToolPen, true),
+                key(Key::ButtonTouch, true),
+                key(Key::ButtonStylus, true),
+                absolute(AbsoluteAxis::Pressure, 300),
+                sync(SynchronizeKind::Report),
+            ],
+        );
+        assert_eq!(output.len(), 5);
+        assert_eq!(tracker.state().phase(), PenPhase::Touching);
+
+        let output = process(
+            &mut tracker,
+            vec![
+                absolute(AbsoluteAxis::X, 10),
+                sync(SynchronizeKind::Dropped),
+                absolute(AbsoluteAxis::X, 20),
+                sync(SynchronizeKind::Report),
+            ],
+        );
+        assert_eq!(
+            output,
+            vec![
+                absolute(AbsoluteAxis::X, 10),
+                key(Key::ButtonStylus, false),
+                key(Key::ButtonTouch, false),
+                key(Key::ButtonToolPen, false),
+                sync(SynchronizeKind::Report),
+            ]
+        );
+        assert_eq!(tracker.state().phase(), PenPhase::OutOfRange);
+
+        let output = process(
+            &mut tracker,
+            vec![
+                absolute(AbsoluteAxis::X, 30),
+                absolute(AbsoluteAxis::Pressure, 200),
+                sync(SynchronizeKind::Report),
+            ],
+        );
+        assert_eq!(
+            output,
+            vec![
+                key(Key::ButtonToolPen, true),
+                absolute(AbsoluteAxis::X, 30),
+                key(Key::ButtonTouch, true),
+                absolute(AbsoluteAxis::Pressure, 200),
+                sync(SynchronizeKind::Report),
+            ]
+        );
+        assert_eq!(tracker.state().phase(), PenPhase::Touching);
+
+        let output = process(&mut tracker, vec![key(Key::ButtonToolRubber, true)]);
+        assert_eq!(
+            output,
+            vec![
+                key(Key::ButtonTouch, false),
+                key(Key::ButtonToolPen, false),
+                key(Key::ButtonToolRubber, true)
+            ]
+        );
+
+        assert_eq!(
+            tracker.release(),
+            vec![
+                key(Key::ButtonToolRubber, false),
+                sync(SynchronizeKind::Report)
+            ]
+        );
+        assert_eq!(tracker.release(), vec![]);
+    }
+}
@@ -0,0 +1,207 @@
+use input_linux::{
+    AbsoluteAxis, Event, EventTime, Key, KeyEvent, RelativeAxis, RelativeEvent, SynchronizeEvent,
+    SynchronizeKind,
+};
+
+// Converts absolute pen motion into mouse deltas, lifting the pen out of range acts as a clutch.
+pub struct RelativeMotion {
+    speed: f64,
+    acceleration: f64,
+    in_range: bool,
+    position: (Option<i32>, Option<i32>),
+    last: Option<(i32, i32, i64)>,
+    remainder: (f64, f64),
+    buttons: Vec<Event>,
+}
+
+impl RelativeMotion {
+    pub fn new(speed: f64, acceleration: f64) -> Self {
+        RelativeMotion {
+            speed,
+            acceleration,
+            in_range: false,
+            position: (None, None),
+            last: None,
+            remainder: (0.0, 0.0),
+            buttons: vec![],
+        }
+    }
+
+    pub fn process(&mut self, event: Event) -> Vec<Event> {
+        match event {
+            Event::Absolute(absolute_event) => {
+                match absolute_event.axis {
+                    AbsoluteAxis::X => self.position.0 = Some(absolute_event.value),
+                    AbsoluteAxis::Y => self.position.1 = Some(absolute_event.value),
+                    _ => {}
+                }
+                vec![]
+            }
+            Event::Key(key_event) => {
+                match key_event.key {
+                    Key::ButtonToolPen | Key::ButtonToolRubber => {
+                        self.in_range = key_event.value.is_pressed();
+                        if !self.in_range {
+                            self.last = None;
+                            self.remainder = (0.0, 0.0);
+                        }
+                    }
+                    Key::ButtonTouch => self.push_button(key_event, Key::ButtonLeft),
+                    Key::ButtonStylus => self.push_button(key_event, Key::ButtonRight),
+                    Key::ButtonStylus2 => self.push_button(key_event, Key::ButtonMiddle),
+                    _ => {}
+                }
+                vec![]
+            }
+            Event::Synchronize(sync_event) if sync_event.kind == SynchronizeKind::Report => {
+                self.flush(sync_event.time)
+            }
+            _ => vec![],
+        }
+    }
+
+    fn push_button(&mut self, key_event: KeyEvent, button: Key) {
+        self.buttons.push(Event::Key(KeyEvent::new(
+            key_event.time,
+            button,
+            key_event.value,
+        )));
+    }
+
+    fn flush(&mut self, time: EventTime) -> Vec<Event> {
+        let mut output = std::mem::take(&mut self.buttons);
+        let now = time.seconds() * 1_000_000 + time.microseconds();
+
+        if let (true, (Some(x), Some(y))) = (self.in_range, self.position) {
+            if let Some((last_x, last_y, last_time)) = self.last {
+                let (dx, dy) = ((x - last_x) as f64, (y - last_y) as f64);
+                let elapsed_ms = ((now - last_time) as f64 / 1000.0).max(1.0);
+                let velocity = dx.hypot(dy) / elapsed_ms;
+                let gain = self.speed * (1.0 + self.acceleration * velocity);
+
+                let rel_x = dx * gain + self.remainder.0;
+                let rel_y = dy * gain + self.remainder.1;
+                self.remainder = (rel_x.fract(), rel_y.fract());
+
+                if rel_x.trunc() != 0.0 {
+                    output.push(Event::Relative(RelativeEvent::new(
+                        time,
+                        RelativeAxis::X,
+                        rel_x.trunc() as i32,
+                    )));
+                }
+                if rel_y.trunc() != 0.0 {
+                    output.push(Event::Relative(RelativeEvent::new(
+                        time,
+                        RelativeAxis::Y,
+                        rel_y.trunc() as i32,
+                    )));
+                }
+            }
+
+            self.last = Some((x, y, now));
+        }
+
+        if !output.is_empty() {
+            output.push(Event::Synchronize(SynchronizeEvent::report(time)));
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input_linux::{AbsoluteEvent, KeyState};
+    use pretty_assertions::assert_eq;
+
+    fn time(ms: i64) -> EventTime {
+        EventTime::new(ms / 1000, (ms % 1000) * 1000)
+    }
+
+    fn frame(motion: &mut RelativeMotion, ms: i64, events: Vec<Event>) -> Vec<Event> {
+        let mut output = vec![];
+        for event in events {
+            output.extend(motion.process(event));
+        }
+        output.extend(motion.process(Event::Synchronize(SynchronizeEvent::report(time(ms)))));
+        output
+    }
+
+    fn position(ms: i64, x: i32, y: i32) -> Vec<Event> {
+        vec![
+            Event::Absolute(AbsoluteEvent::new(time(ms), AbsoluteAxis::X, x)),
+            Event::Absolute(AbsoluteEvent::new(time(ms), AbsoluteAxis::Y, y)),
+        ]
+    }
+
+    fn tool(ms: i64, in_range: bool) -> Event {
+        Event::Key(KeyEvent::new(
+            time(ms),
+            Key::ButtonToolPen,
+            KeyState::pressed(in_range),
+        ))
+    }
+
+    #[test]
+    fn it_converts_motion_to_deltas() {
+        let mut motion = RelativeMotion::new(0.5, 0.0);
+
+        let mut events = position(0, 1000, 1000);
+        events.push(tool(0, true));
+        assert_eq!(frame(&mut motion, 0, events), vec![]);
+
+        assert_eq!(
+            frame(&mut motion, 10, position(10, 1010, 995)),
+            vec![
+                Event::Relative(RelativeEvent::new(time(10), RelativeAxis::X, 5)),
+                Event::Relative(RelativeEvent::new(time(10), RelativeAxis::Y, -2)),
+                Event::Synchronize(SynchronizeEvent::report(time(10))),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_uses_pen_lift_as_clutch() {
+        let mut motion = RelativeMotion::new(1.0, 0.0);
+
+        let mut events = position(0, 1000, 1000);
+        events.push(tool(0, true));
+        frame(&mut motion, 0, events);
+        frame(&mut motion, 10, vec![tool(10, false)]);
+
+        let mut events = position(20, 5000, 5000);
+        events.push(tool(20, true));
+        assert_eq!(frame(&mut motion, 20, events), vec![]);
+
+        assert_eq!(
+            frame(&mut motion, 30, position(30, 5003, 5000)),
+            vec![
+                Event::Relative(RelativeEvent::new(time(30), RelativeAxis::X, 3)),
+                Event::Synchronize(SynchronizeEvent::report(time(30))),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_maps_pen_buttons_to_mouse_buttons() {
+        let mut motion = RelativeMotion::new(1.0, 0.0);
+
+        assert_eq!(
+            frame(
+                &mut motion,
+                0,
+                vec![Event::Key(KeyEvent::new(
+                    time(0),
+                    Key::ButtonTouch,
+                    KeyState::PRESSED
+                ))]
+            ),
+            vec![
+                Event::Key(KeyEvent::new(time(0), Key::ButtonLeft, KeyState::PRESSED)),
+                Event::Synchronize(SynchronizeEvent::report(time(0))),
+            ]
+        );
+    }
+}
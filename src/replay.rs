@@ -0,0 +1,162 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::Error,
+    source::{EventSource, DESCRIBE_COMMAND},
+};
+
+// Replays a captured `getevent -t` stream from a file or stdin ("-"), the subdevices being
+// described by a captured `getevent -p` or `getevent -i` output.
+pub struct ReplaySource {
+    input: PathBuf,
+    description: PathBuf,
+    realtime: bool,
+    stdin_taken: AtomicBool,
+}
+
+impl ReplaySource {
+    pub fn new(input: PathBuf, description: PathBuf, realtime: bool) -> Self {
+        ReplaySource {
+            input,
+            description,
+            realtime,
+            stdin_taken: AtomicBool::new(false),
+        }
+    }
+
+    fn is_stdin(&self) -> bool {
+        self.input.to_str() == Some("-")
+    }
+
+    fn open_input(&self) -> Result<Box<dyn BufRead>, Error> {
+        if !self.is_stdin() {
+            return Ok(Box::new(BufReader::new(File::open(&self.input)?)));
+        }
+
+        if self.stdin_taken.swap(true, Ordering::SeqCst) {
+            return Err(Error::InvalidArguments(String::from(
+                "stdin can only be replayed once, save the events to a file to forward several subdevices",
+            )));
+        }
+
+        Ok(Box::new(io::stdin().lock()))
+    }
+}
+
+impl EventSource for ReplaySource {
+    fn name(&self) -> String {
+        match self.is_stdin() {
+            true => String::from("stdin"),
+            false => self.input.display().to_string(),
+        }
+    }
+
+    // Only answers the describe command, from the description file
+    fn shell(&self, command: &[&str], output: &mut dyn Write) -> Result<(), Error> {
+        if command != DESCRIBE_COMMAND {
+            return Err(Error::InvalidArguments(format!(
+                "{} cannot run `{}`",
+                self.name(),
+                command.join(" ")
+            )));
+        }
+
+        io::copy(&mut File::open(&self.description)?, output)?;
+        Ok(())
+    }
+
+    fn stream_events(&self, device_path: &str, output: &mut dyn Write) -> Result<(), Error> {
+        let mut started_opt: Option<(Instant, f64)> = None;
+
+        for line in self.open_input()?.lines() {
+            let line = line?;
+
+            // Skips subdevice announcements, getevent -t starts events with their timestamp
+            let Some((timestamp, event)) = line.strip_prefix('[').and_then(|x| x.split_once(']'))
+            else {
+                continue;
+            };
+
+            // Present when getevent reads several subdevices at once
+            if let Some((path, _)) = event.trim_start().split_once(": ") {
+                if path != device_path {
+                    continue;
+                }
+            }
+
+            if let (true, Ok(timestamp)) = (self.realtime, timestamp.trim().parse::<f64>()) {
+                let (started_at, first) = *started_opt.get_or_insert((Instant::now(), timestamp));
+                let due = Duration::from_secs_f64((timestamp - first).max(0.0));
+                if let Some(delay) = due.checked_sub(started_at.elapsed()) {
+                    std::thread::sleep(delay);
+                }
+            }
+
+            writeln!(output, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_input_event;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_replays_captured_events() {
+        let source = ReplaySource::new(
+            PathBuf::from("events.txt"),
+            PathBuf::from("devices.txt"),
+            false,
+        );
+        let devices = source.describe().unwrap();
+        assert_eq!(devices[4].name, "sec_e-pen");
+
+        let mut output = vec![];
+        source.stream_events(&devices[4].path, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output, include_str!("../events.txt"));
+        assert!(source.stream_events(&devices[4].path, &mut vec![]).is_ok());
+
+        let input = std::env::temp_dir().join(format!("atb-replay-{}.txt", std::process::id()));
+        std::fs::write(
+            &input,
+            "add device 1: /dev/input/event9\n  \
+               name:     \"sec_e-pen\"\n\
+             [ 1338055.981824] /dev/input/event9: 0003 0000 00002c6a\n\
+             [ 1338055.981900] /dev/input/event2: 0002 0006 00000001\n\
+             [ 1338055.991824] /dev/input/event9: 0000 0000 00000000\n",
+        )
+        .unwrap();
+        let source = ReplaySource::new(input.clone(), PathBuf::from("devices.txt"), true);
+
+        let mut output = vec![];
+        let started_at = Instant::now();
+        source
+            .stream_events("/dev/input/event9", &mut output)
+            .unwrap();
+        std::fs::remove_file(input).unwrap();
+
+        assert!(started_at.elapsed() >= Duration::from_millis(10));
+        let lines: Vec<&str> = std::str::from_utf8(&output).unwrap().lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "[ 1338055.981824] /dev/input/event9: 0003 0000 00002c6a",
+                "[ 1338055.991824] /dev/input/event9: 0000 0000 00000000"
+            ]
+        );
+        assert!(lines
+            .iter()
+            .all(|x| matches!(parse_input_event(x), Ok((_, Some(_))))));
+    }
+}
@@ -0,0 +1,628 @@
+use std::{
+    fs,
+    io::Write,
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    path::PathBuf,
+    process::Command,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use clap::ValueEnum;
+use image::{
+    codecs::jpeg::JpegEncoder,
+    imageops::{self, FilterType},
+    ExtendedColorType, RgbImage,
+};
+use input_linux::{AbsoluteAxis, Event};
+use log::{info, warn};
+
+use crate::{error::Error, signals, source::EventSource};
+
+// Frames sent to the companion app, all integers are big endian:
+//   hello: "ATBS" version:u8 width:u16 height:u16
+//   frame: tile_count:u16 tile_count * (x:u16 y:u16 width:u16 height:u16 encoding:u8 length:u32 data)
+// Frames are already scaled to the tablet screen, only the tiles changed since the previous
+// frame are sent, the first frame after connecting contains all of them.
+const MAGIC: &[u8; 4] = b"ATBS";
+const VERSION: u8 = 1;
+const TILE_SIZE: u32 = 64;
+
+const TILE_RAW: u8 = 0;
+const TILE_JPEG: u8 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    // <width>x<height>+<x>+<y>, like X geometries
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let error = || format!("Expected <width>x<height>+<x>+<y>, got '{}'", input);
+
+        let mut parts = input.split('+');
+        let (width, height) = parse_size(parts.next().unwrap_or_default())?;
+        let x = parts
+            .next()
+            .map_or(Ok(0), |x| x.parse().map_err(|_| error()))?;
+        let y = parts
+            .next()
+            .map_or(Ok(0), |x| x.parse().map_err(|_| error()))?;
+
+        if parts.next().is_some() {
+            return Err(error());
+        }
+
+        Ok(Region {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+}
+
+pub fn parse_size(input: &str) -> Result<(u32, u32), String> {
+    input
+        .split_once('x')
+        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+        .filter(|(width, height)| *width > 0 && *height > 0)
+        .ok_or_else(|| format!("Expected <width>x<height>, got '{}'", input))
+}
+
+// `wm size` prints the physical size and, when set, an override which is the one in use.
+fn parse_wm_size(output: &str) -> Option<(u32, u32)> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once("size:"))
+        .filter_map(|(_, size)| parse_size(size.trim()).ok())
+        .next_back()
+}
+
+pub trait ScreenCapture: Send {
+    fn capture(&mut self) -> Result<RgbImage, Error>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CaptureBackend {
+    Command(String),
+    Files(Vec<PathBuf>),
+}
+
+impl FromStr for CaptureBackend {
+    type Err = String;
+
+    // command:<shell command writing an image on stdout> | files:<path>[,<path>...]
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.split_once(':') {
+            Some(("command", command)) => Ok(CaptureBackend::Command(command.to_string())),
+            Some(("files", paths)) => Ok(CaptureBackend::Files(
+                paths.split(',').map(PathBuf::from).collect(),
+            )),
+            _ => Err(format!(
+                "Expected command:<command> or files:<path>[,<path>...], got '{}'",
+                input
+            )),
+        }
+    }
+}
+
+impl CaptureBackend {
+    pub fn open(&self) -> Result<Box<dyn ScreenCapture>, Error> {
+        match self {
+            CaptureBackend::Command(command) => Ok(Box::new(CommandCapture {
+                command: command.clone(),
+            })),
+            CaptureBackend::Files(paths) => {
+                let mut files = vec![];
+                for path in paths {
+                    if path.is_dir() {
+                        let mut entries = fs::read_dir(path)?
+                            .filter_map(|entry| entry.ok().map(|x| x.path()))
+                            .filter(|x| x.is_file())
+                            .collect::<Vec<_>>();
+                        entries.sort();
+                        files.extend(entries);
+                    } else {
+                        files.push(path.clone());
+                    }
+                }
+
+                if files.is_empty() {
+                    return Err(Error::Capture(String::from(
+                        "no image files to capture from",
+                    )));
+                }
+
+                Ok(Box::new(ImageFilesCapture { files, index: 0 }))
+            }
+        }
+    }
+}
+
+struct CommandCapture {
+    command: String,
+}
+
+impl ScreenCapture for CommandCapture {
+    fn capture(&mut self) -> Result<RgbImage, Error> {
+        let output = Command::new("sh").arg("-c").arg(&self.command).output()?;
+
+        if !output.status.success() {
+            return Err(Error::Capture(format!(
+                "`{}` exited with {}: {}",
+                self.command,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        image::load_from_memory(&output.stdout)
+            .map(|x| x.to_rgb8())
+            .map_err(|err| Error::Capture(format!("`{}` output: {}", self.command, err)))
+    }
+}
+
+// Cycles through image files, one per capture, to replay screens without a display server.
+struct ImageFilesCapture {
+    files: Vec<PathBuf>,
+    index: usize,
+}
+
+impl ScreenCapture for ImageFilesCapture {
+    fn capture(&mut self) -> Result<RgbImage, Error> {
+        let path = &self.files[self.index % self.files.len()];
+        self.index += 1;
+
+        image::open(path)
+            .map(|x| x.to_rgb8())
+            .map_err(|err| Error::Capture(format!("{}: {}", path.display(), err)))
+    }
+}
+
+// Where the captured region of the host screen ends up on the tablet screen, scaled to fit
+// while keeping its aspect ratio.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SidecarLayout {
+    pub region: Region,
+    pub screen: (u32, u32),
+    pub tablet: (u32, u32),
+    pub placement: Region,
+}
+
+impl SidecarLayout {
+    pub fn new(region: Region, screen: (u32, u32), tablet: (u32, u32)) -> Result<Self, Error> {
+        // Checked as the region comes from the command line
+        let right = region.x.checked_add(region.width);
+        let bottom = region.y.checked_add(region.height);
+        if right.is_none_or(|x| x > screen.0) || bottom.is_none_or(|x| x > screen.1) {
+            return Err(Error::InvalidArguments(format!(
+                "Sidecar region {}x{}+{}+{} does not fit in the {}x{} screen",
+                region.width, region.height, region.x, region.y, screen.0, screen.1
+            )));
+        }
+
+        let scale = f64::min(
+            tablet.0 as f64 / region.width as f64,
+            tablet.1 as f64 / region.height as f64,
+        );
+        let width = ((region.width as f64 * scale).round() as u32).clamp(1, tablet.0);
+        let height = ((region.height as f64 * scale).round() as u32).clamp(1, tablet.1);
+
+        Ok(SidecarLayout {
+            region,
+            screen,
+            tablet,
+            placement: Region {
+                x: (tablet.0 - width) / 2,
+                y: (tablet.1 - height) / 2,
+                width,
+                height,
+            },
+        })
+    }
+
+    pub fn render(&self, screen: &RgbImage) -> RgbImage {
+        let region = imageops::crop_imm(
+            screen,
+            self.region.x,
+            self.region.y,
+            self.region.width,
+            self.region.height,
+        )
+        .to_image();
+        let scaled = imageops::resize(
+            &region,
+            self.placement.width,
+            self.placement.height,
+            FilterType::Triangle,
+        );
+
+        let mut frame = RgbImage::new(self.tablet.0, self.tablet.1);
+        imageops::replace(
+            &mut frame,
+            &scaled,
+            self.placement.x as i64,
+            self.placement.y as i64,
+        );
+        frame
+    }
+}
+
+// Remaps pen positions so that touching the tablet lands on the host pixel displayed under
+// the pen, assuming the virtual tablet is mapped to the whole captured screen.
+pub struct InputAlignment {
+    layout: SidecarLayout,
+    maximum_x: i32,
+    maximum_y: i32,
+}
+
+impl InputAlignment {
+    pub fn new(layout: SidecarLayout, maximum_x: i32, maximum_y: i32) -> Self {
+        InputAlignment {
+            layout,
+            maximum_x,
+            maximum_y,
+        }
+    }
+
+    pub fn align(&self, event: Event) -> Event {
+        match event {
+            Event::Absolute(mut absolute_event) => {
+                let (maximum, tablet, placement, region, screen) = match absolute_event.axis {
+                    AbsoluteAxis::X => (
+                        self.maximum_x,
+                        self.layout.tablet.0,
+                        (self.layout.placement.x, self.layout.placement.width),
+                        (self.layout.region.x, self.layout.region.width),
+                        self.layout.screen.0,
+                    ),
+                    AbsoluteAxis::Y => (
+                        self.maximum_y,
+                        self.layout.tablet.1,
+                        (self.layout.placement.y, self.layout.placement.height),
+                        (self.layout.region.y, self.layout.region.height),
+                        self.layout.screen.1,
+                    ),
+                    _ => return event,
+                };
+
+                let tablet_pixel = absolute_event.value as f64 / maximum as f64 * tablet as f64;
+                let relative =
+                    ((tablet_pixel - placement.0 as f64) / placement.1 as f64).clamp(0.0, 1.0);
+                let screen_pixel = region.0 as f64 + relative * region.1 as f64;
+                absolute_event.value =
+                    (screen_pixel / screen as f64 * maximum as f64).round() as i32;
+
+                Event::Absolute(absolute_event)
+            }
+            _ => event,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Encoding {
+    Jpeg,
+    Raw,
+}
+
+pub struct FrameEncoder {
+    encoding: Encoding,
+    quality: u8,
+    previous: Option<RgbImage>,
+}
+
+impl FrameEncoder {
+    pub fn new(encoding: Encoding, quality: u8) -> Self {
+        FrameEncoder {
+            encoding,
+            quality,
+            previous: None,
+        }
+    }
+
+    // Returns None when nothing changed since the previous frame.
+    pub fn encode(&mut self, frame: &RgbImage) -> Result<Option<Vec<u8>>, Error> {
+        let mut tiles = vec![];
+
+        for y in (0..frame.height()).step_by(TILE_SIZE as usize) {
+            for x in (0..frame.width()).step_by(TILE_SIZE as usize) {
+                let width = TILE_SIZE.min(frame.width() - x);
+                let height = TILE_SIZE.min(frame.height() - y);
+                let tile = imageops::crop_imm(frame, x, y, width, height).to_image();
+
+                let unchanged = self.previous.as_ref().is_some_and(|previous| {
+                    previous.dimensions() == frame.dimensions()
+                        && *imageops::crop_imm(previous, x, y, width, height).to_image() == *tile
+                });
+                if !unchanged {
+                    tiles.push((x, y, tile));
+                }
+            }
+        }
+
+        self.previous = Some(frame.clone());
+
+        if tiles.is_empty() {
+            return Ok(None);
+        }
+
+        let mut output = vec![];
+        output.extend_from_slice(&(tiles.len() as u16).to_be_bytes());
+
+        for (x, y, tile) in tiles {
+            for value in [x, y, tile.width(), tile.height()] {
+                output.extend_from_slice(&(value as u16).to_be_bytes());
+            }
+
+            let (kind, data) = match self.encoding {
+                Encoding::Raw => (TILE_RAW, tile.into_raw()),
+                Encoding::Jpeg => {
+                    let mut data = vec![];
+                    JpegEncoder::new_with_quality(&mut data, self.quality)
+                        .encode(
+                            tile.as_raw(),
+                            tile.width(),
+                            tile.height(),
+                            ExtendedColorType::Rgb8,
+                        )
+                        .map_err(|err| Error::Capture(err.to_string()))?;
+                    (TILE_JPEG, data)
+                }
+            };
+
+            output.push(kind);
+            output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            output.extend_from_slice(&data);
+        }
+
+        Ok(Some(output))
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct SidecarArgs {
+    #[arg(long = "sidecar", value_name = "WIDTHxHEIGHT+X+Y")]
+    region: Option<Region>,
+
+    #[arg(long, value_name = "BACKEND", default_value = "command:grim -t ppm -")]
+    capture: CaptureBackend,
+
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_size)]
+    sidecar_size: Option<(u32, u32)>,
+
+    #[arg(long, default_value_t = 15)]
+    sidecar_fps: u32,
+
+    #[arg(long, value_enum, default_value_t = Encoding::Jpeg)]
+    sidecar_encoding: Encoding,
+
+    #[arg(long, default_value_t = 80)]
+    sidecar_quality: u8,
+
+    #[arg(long, value_name = "PORT", default_value_t = 27185)]
+    sidecar_port: u16,
+}
+
+impl SidecarArgs {
+    // Starts streaming when a region is set and returns where it is displayed on the tablet.
+    pub fn spawn(
+        &self,
+        source: &dyn EventSource,
+        rotated: bool,
+    ) -> Result<Option<SidecarLayout>, Error> {
+        let region = match self.region {
+            None => return Ok(None),
+            Some(region) => region,
+        };
+
+        let tablet = match self.sidecar_size {
+            Some(size) => size,
+            None => {
+                let mut output = vec![];
+                source
+                    .shell(&["wm", "size"], &mut output)
+                    .ok()
+                    .and_then(|_| parse_wm_size(&String::from_utf8_lossy(&output)))
+                    .map(|(width, height)| {
+                        if rotated {
+                            (height, width)
+                        } else {
+                            (width, height)
+                        }
+                    })
+                    .ok_or_else(|| {
+                        Error::InvalidArguments(String::from(
+                            "Could not query the tablet screen size, set it with --sidecar-size",
+                        ))
+                    })?
+            }
+        };
+
+        let mut capture = self.capture.open()?;
+        let first = capture.capture()?;
+        let layout = SidecarLayout::new(region, first.dimensions(), tablet)?;
+
+        let listener =
+            TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, self.sidecar_port)))?;
+        if let Err(err) = source.reverse_port(self.sidecar_port) {
+            warn!("Could not set up the sidecar stream on the device: {}", err);
+        }
+        info!(
+            "Streaming {}x{}+{}+{} to the companion app at {}x{} on port {}",
+            region.width, region.height, region.x, region.y, tablet.0, tablet.1, self.sidecar_port
+        );
+
+        let thread_layout = layout.clone();
+        let interval = Duration::from_secs_f64(1.0 / self.sidecar_fps.max(1) as f64);
+        let (encoding, quality) = (self.sidecar_encoding, self.sidecar_quality);
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if signals::stop_requested() {
+                    break;
+                }
+
+                match stream {
+                    Ok(stream) => {
+                        let mut encoder = FrameEncoder::new(encoding, quality);
+                        if let Err(err) = stream_frames(
+                            stream,
+                            capture.as_mut(),
+                            &thread_layout,
+                            &mut encoder,
+                            interval,
+                        ) {
+                            info!("Sidecar stream stopped: {}", err);
+                        }
+                    }
+                    Err(err) => warn!("Could not accept sidecar connection: {}", err),
+                }
+            }
+        });
+
+        Ok(Some(layout))
+    }
+}
+
+fn stream_frames(
+    mut stream: TcpStream,
+    capture: &mut dyn ScreenCapture,
+    layout: &SidecarLayout,
+    encoder: &mut FrameEncoder,
+    interval: Duration,
+) -> Result<(), Error> {
+    stream.set_nodelay(true)?;
+
+    let mut hello = MAGIC.to_vec();
+    hello.push(VERSION);
+    hello.extend_from_slice(&(layout.tablet.0 as u16).to_be_bytes());
+    hello.extend_from_slice(&(layout.tablet.1 as u16).to_be_bytes());
+    stream.write_all(&hello)?;
+
+    while !signals::stop_requested() {
+        let started_at = Instant::now();
+
+        match capture.capture() {
+            Ok(screen) if screen.dimensions() == layout.screen => {
+                if let Some(frame) = encoder.encode(&layout.render(&screen))? {
+                    stream.write_all(&frame)?;
+                }
+            }
+            Ok(screen) => warn!(
+                "Captured screen changed size to {}x{}, restart to realign the sidecar region",
+                screen.width(),
+                screen.height()
+            ),
+            Err(err) => warn!("{}", err),
+        }
+
+        std::thread::sleep(interval.saturating_sub(started_at.elapsed()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input_linux::{AbsoluteEvent, EventTime};
+    use pretty_assertions::assert_eq;
+
+    fn apply_frame(canvas: &mut RgbImage, mut frame: &[u8]) -> usize {
+        let mut take = |length: usize| {
+            let (value, rest) = frame.split_at(length);
+            frame = rest;
+            value.to_vec()
+        };
+        let u16_at = |bytes: Vec<u8>| u16::from_be_bytes([bytes[0], bytes[1]]) as u32;
+
+        let tile_count = u16_at(take(2));
+        for _ in 0..tile_count {
+            let (x, y) = (u16_at(take(2)), u16_at(take(2)));
+            let (width, height) = (u16_at(take(2)), u16_at(take(2)));
+            assert_eq!(take(1), vec![TILE_RAW]);
+            let length = u32::from_be_bytes(take(4).try_into().unwrap()) as usize;
+            let tile = RgbImage::from_raw(width, height, take(length)).unwrap();
+            imageops::replace(canvas, &tile, x as i64, y as i64);
+        }
+
+        tile_count as usize
+    }
+
+    #[test]
+    fn it_streams_changed_tiles_of_the_captured_region() {
+        let directory = std::env::temp_dir().join(format!("atb-sidecar-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        let mut screen = RgbImage::from_pixel(400, 300, image::Rgb([20, 40, 60]));
+        screen.save(directory.join("0.png")).unwrap();
+        screen.put_pixel(150, 120, image::Rgb([255, 255, 255]));
+        screen.save(directory.join("1.png")).unwrap();
+
+        let mut capture = CaptureBackend::from_str(&format!("files:{}", directory.display()))
+            .unwrap()
+            .open()
+            .unwrap();
+        let region = "200x100+100+100".parse::<Region>().unwrap();
+        let layout = SidecarLayout::new(region, (400, 300), (200, 200)).unwrap();
+        let overflowing = "2x2+4294967295+0".parse::<Region>().unwrap();
+        assert!(SidecarLayout::new(overflowing, (400, 300), (200, 200)).is_err());
+        assert_eq!(
+            layout.placement,
+            Region {
+                x: 0,
+                y: 50,
+                width: 200,
+                height: 100
+            }
+        );
+
+        let mut encoder = FrameEncoder::new(Encoding::Raw, 80);
+        let mut canvas = RgbImage::new(200, 200);
+
+        let first = layout.render(&capture.capture().unwrap());
+        let frame = encoder.encode(&first).unwrap().unwrap();
+        assert_eq!(apply_frame(&mut canvas, &frame), 16);
+        assert_eq!(canvas, first);
+        assert_eq!(encoder.encode(&first).unwrap(), None);
+
+        let second = layout.render(&capture.capture().unwrap());
+        let frame = encoder.encode(&second).unwrap().unwrap();
+        assert_eq!(apply_frame(&mut canvas, &frame), 1);
+        assert_eq!(canvas, second);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn it_aligns_pen_with_displayed_region() {
+        let region = "200x100+100+100".parse::<Region>().unwrap();
+        let layout = SidecarLayout::new(region, (400, 300), (200, 200)).unwrap();
+        let alignment = InputAlignment::new(layout, 2000, 2000);
+        let time = EventTime::new(0, 0);
+        let align = |axis, value| match alignment
+            .align(Event::Absolute(AbsoluteEvent::new(time, axis, value)))
+        {
+            Event::Absolute(event) => event.value,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(align(AbsoluteAxis::X, 0), 500);
+        assert_eq!(align(AbsoluteAxis::X, 2000), 1500);
+        assert_eq!(align(AbsoluteAxis::Y, 1000), 1000);
+        assert_eq!(align(AbsoluteAxis::Y, 0), 667);
+
+        assert_eq!(
+            parse_wm_size("Physical size: 1752x2800\nOverride size: 1200x1920\n"),
+            Some((1200, 1920))
+        );
+    }
+}
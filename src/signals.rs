@@ -0,0 +1,40 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use nix::{
+    libc::c_int,
+    sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal},
+};
+
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+static REPORT_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle_signal(signal: c_int) {
+    if signal == Signal::SIGUSR1 as c_int {
+        REPORT_REQUESTED.store(true, Ordering::SeqCst);
+    } else {
+        STOP_REQUESTED.store(true, Ordering::SeqCst);
+    }
+}
+
+pub fn install_handlers() -> nix::Result<()> {
+    let action = SigAction::new(
+        SigHandler::Handler(handle_signal),
+        SaFlags::SA_RESTART,
+        SigSet::empty(),
+    );
+
+    for signal in [Signal::SIGINT, Signal::SIGTERM, Signal::SIGUSR1] {
+        // The handler only touches atomics so it is async-signal-safe.
+        unsafe { sigaction(signal, &action) }?;
+    }
+
+    Ok(())
+}
+
+pub fn stop_requested() -> bool {
+    STOP_REQUESTED.load(Ordering::SeqCst)
+}
+
+pub fn take_report_request() -> bool {
+    REPORT_REQUESTED.swap(false, Ordering::SeqCst)
+}
@@ -0,0 +1,289 @@
+use std::{
+    io::Write,
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use adb_client::{ADBDeviceExt, ADBServer, ADBServerDevice};
+use log::{info, warn};
+
+use crate::{
+    adb_native::{default_key_path, AdbKey, NativeAdbSource},
+    command::{CommandSource, StreamFormat},
+    companion::{start_companion_app, CompanionSource},
+    discovery::{discover, CONNECT_SERVICE},
+    error::Error,
+    parser::{parse_devices, parse_devices_lenient, ADBDevice},
+    replay::ReplaySource,
+};
+
+pub type SharedSource = Arc<dyn EventSource>;
+
+pub const DESCRIBE_COMMAND: [&str; 2] = ["getevent", "-i"];
+
+pub trait EventSource: Send + Sync {
+    fn name(&self) -> String;
+
+    fn shell(&self, command: &[&str], output: &mut dyn Write) -> Result<(), Error>;
+
+    fn describe(&self) -> Result<Vec<ADBDevice>, Error> {
+        let mut output = vec![];
+        self.shell(&DESCRIBE_COMMAND, &mut output)?;
+
+        let response = String::from_utf8_lossy(&output);
+        parse_devices(&response).map_err(|err| Error::parse("getevent -i output", err))
+    }
+
+    fn stream_events(&self, device_path: &str, output: &mut dyn Write) -> Result<(), Error> {
+        self.shell(&["getevent", "-t", device_path], output)
+    }
+
+    fn reverse_port(&self, port: u16) -> Result<(), Error> {
+        Err(Error::InvalidArguments(format!(
+            "{} cannot reverse ports, run `adb reverse tcp:{1} tcp:{1}`",
+            self.name(),
+            port
+        )))
+    }
+}
+
+pub struct AdbServerSource {
+    identifier: String,
+}
+
+impl AdbServerSource {
+    pub fn new(identifier: String) -> Self {
+        AdbServerSource { identifier }
+    }
+}
+
+impl EventSource for AdbServerSource {
+    fn name(&self) -> String {
+        self.identifier.clone()
+    }
+
+    fn shell(&self, command: &[&str], output: &mut dyn Write) -> Result<(), Error> {
+        ADBServerDevice::new(self.identifier.clone(), None)
+            .shell_command(command.iter(), output)?;
+        Ok(())
+    }
+
+    fn reverse_port(&self, port: u16) -> Result<(), Error> {
+        reverse_port(&self.identifier, port)
+    }
+}
+
+// Describes whatever subdevices can be parsed and warns about the others, for vendor getevent
+// builds printing blocks the parser does not understand.
+pub struct LenientSource {
+    inner: SharedSource,
+}
+
+impl EventSource for LenientSource {
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    fn shell(&self, command: &[&str], output: &mut dyn Write) -> Result<(), Error> {
+        self.inner.shell(command, output)
+    }
+
+    fn describe(&self) -> Result<Vec<ADBDevice>, Error> {
+        let mut output = vec![];
+        self.inner.shell(&DESCRIBE_COMMAND, &mut output)?;
+
+        let (subdevices, skipped) = parse_devices_lenient(&String::from_utf8_lossy(&output));
+        for skipped in skipped {
+            warn!(
+                "Skipping subdevice of {} ({}): {}",
+                self.name(),
+                skipped.header,
+                skipped.reason
+            );
+        }
+
+        Ok(subdevices)
+    }
+
+    fn stream_events(&self, device_path: &str, output: &mut dyn Write) -> Result<(), Error> {
+        self.inner.stream_events(device_path, output)
+    }
+
+    fn reverse_port(&self, port: u16) -> Result<(), Error> {
+        self.inner.reverse_port(port)
+    }
+}
+
+pub fn reverse_port(identifier: &str, port: u16) -> Result<(), Error> {
+    ADBServerDevice::new(identifier.to_string(), None)
+        .reverse(format!("tcp:{}", port), format!("tcp:{}", port))?;
+    Ok(())
+}
+
+#[derive(clap::Args, Debug)]
+pub struct SourceArgs {
+    #[arg(long)]
+    device: Option<String>,
+
+    #[arg(long, value_name = "HOST:PORT", conflicts_with = "device")]
+    connect: Option<String>,
+
+    #[arg(long, value_name = "PATH", requires = "connect")]
+    adb_key: Option<PathBuf>,
+
+    #[arg(long, conflicts_with_all = ["device", "connect"])]
+    discover: bool,
+
+    #[arg(long, value_name = "SECONDS", default_value_t = 3)]
+    discover_timeout: u64,
+
+    #[arg(
+        long,
+        value_name = "PORT",
+        num_args = 0..=1,
+        default_missing_value = "27183",
+        conflicts_with_all = ["connect", "discover"]
+    )]
+    companion: Option<u16>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        visible_alias = "input-file",
+        requires = "input_description",
+        conflicts_with_all = ["device", "connect", "discover", "companion", "command"]
+    )]
+    input: Option<PathBuf>,
+
+    // Devices streaming without getevent cannot describe their subdevices with it
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["device", "connect", "discover", "companion"],
+        required_if_eq_any = [("command_stream", "raw"), ("command_stream", "evtest")]
+    )]
+    input_description: Option<PathBuf>,
+
+    #[arg(long, requires = "input")]
+    input_realtime: bool,
+
+    #[arg(
+        long,
+        value_name = "PREFIX",
+        conflicts_with_all = ["device", "connect", "discover", "companion"]
+    )]
+    command: Option<String>,
+
+    #[arg(long, value_enum, default_value_t = StreamFormat::Getevent)]
+    command_stream: StreamFormat,
+
+    #[arg(long, conflicts_with = "companion")]
+    lenient: bool,
+}
+
+impl SourceArgs {
+    pub fn is_explicit(&self) -> bool {
+        self.device.is_some()
+            || self.connect.is_some()
+            || self.companion.is_some()
+            || self.input.is_some()
+            || self.command.is_some()
+    }
+
+    pub fn sources(&self) -> Result<Vec<SharedSource>, Error> {
+        let sources = self.open_sources()?;
+
+        if !self.lenient {
+            return Ok(sources);
+        }
+
+        Ok(sources
+            .into_iter()
+            .map(|inner| Arc::new(LenientSource { inner }) as SharedSource)
+            .collect())
+    }
+
+    fn open_sources(&self) -> Result<Vec<SharedSource>, Error> {
+        if let (Some(input), Some(description)) = (&self.input, &self.input_description) {
+            return Ok(vec![Arc::new(ReplaySource::new(
+                input.clone(),
+                description.clone(),
+                self.input_realtime,
+            ))]);
+        }
+
+        if let Some(ref prefix) = self.command {
+            return Ok(vec![Arc::new(CommandSource::new(
+                prefix.clone(),
+                self.command_stream.clone(),
+                self.input_description.clone(),
+            ))]);
+        }
+
+        if let Some(port) = self.companion {
+            let identifier = match self.device {
+                Some(ref identifier) => Some(identifier.clone()),
+                None => ADBServer::default()
+                    .devices_long()
+                    .ok()
+                    .filter(|x| x.len() == 1)
+                    .map(|mut x| x.remove(0).identifier),
+            };
+
+            let source = CompanionSource::bind(
+                SocketAddr::from((Ipv4Addr::LOCALHOST, port)),
+                identifier.clone(),
+            )?;
+
+            match identifier {
+                Some(identifier) => {
+                    if let Err(err) = start_companion_app(&identifier, port) {
+                        warn!("Could not start the companion app on {}: {}", identifier, err);
+                    }
+                }
+                None => warn!(
+                    "No single ADB device to set up, run `adb reverse tcp:{0} tcp:{0}` and start the companion app manually",
+                    port
+                ),
+            }
+
+            return Ok(vec![Arc::new(source)]);
+        }
+
+        match (&self.device, &self.connect) {
+            (_, Some(address)) => {
+                let key_path = self.adb_key.clone().unwrap_or_else(default_key_path);
+                let key = AdbKey::load_or_generate(&key_path)?;
+                Ok(vec![Arc::new(NativeAdbSource::new(
+                    address.clone(),
+                    key,
+                    key_path,
+                ))])
+            }
+            (Some(identifier), None) => {
+                Ok(vec![Arc::new(AdbServerSource::new(identifier.clone()))])
+            }
+            (None, None) => {
+                let mut server = ADBServer::default();
+
+                if self.discover {
+                    let timeout = Duration::from_secs(self.discover_timeout);
+                    for device in discover(&[CONNECT_SERVICE], timeout)? {
+                        info!("Connecting to {} at {}", device.name, device.address);
+                        if let Err(err) = server.connect_device(device.address) {
+                            warn!("Could not connect to {}: {}", device.name, err);
+                        }
+                    }
+                }
+
+                Ok(server
+                    .devices_long()?
+                    .into_iter()
+                    .map(|x| Arc::new(AdbServerSource::new(x.identifier)) as SharedSource)
+                    .collect())
+            }
+        }
+    }
+}
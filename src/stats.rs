@@ -0,0 +1,255 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use input_linux::{Event, EventTime, SynchronizeKind};
+
+use crate::source::EventSource;
+
+const WINDOW: usize = 1024;
+const CLOCK_SAMPLES: usize = 5;
+
+pub struct Stats {
+    epoch: Instant,
+    clock_offset: Option<f64>,
+    latencies: VecDeque<f64>,
+    frame_sizes: VecDeque<usize>,
+    frame_size: usize,
+    events: u64,
+    frames: u64,
+    dropped: u64,
+    parse_failures: u64,
+    last_report: (Instant, u64),
+}
+
+pub struct StatsReport {
+    pub events: u64,
+    pub event_rate: f64,
+    pub frames: u64,
+    pub average_frame_size: f64,
+    pub max_frame_size: usize,
+    pub latency_ms: Option<(f64, f64, f64)>,
+    pub dropped: u64,
+    pub parse_failures: u64,
+}
+
+impl Stats {
+    pub fn new(epoch: Instant, clock_offset: Option<f64>) -> Self {
+        Stats {
+            epoch,
+            clock_offset,
+            latencies: VecDeque::with_capacity(WINDOW),
+            frame_sizes: VecDeque::with_capacity(WINDOW),
+            frame_size: 0,
+            events: 0,
+            frames: 0,
+            dropped: 0,
+            parse_failures: 0,
+            last_report: (epoch, 0),
+        }
+    }
+
+    pub fn record_event(&mut self, event: &Event, received_at: Instant) -> Option<f64> {
+        self.events += 1;
+
+        match event {
+            Event::Synchronize(sync_event) if sync_event.kind == SynchronizeKind::Report => {
+                push_window(&mut self.frame_sizes, self.frame_size);
+                self.frame_size = 0;
+                self.frames += 1;
+
+                self.clock_offset.map(|offset| {
+                    let received = received_at.duration_since(self.epoch).as_secs_f64();
+                    let latency_ms =
+                        (received - (event_seconds(&sync_event.time) - offset)) * 1000.0;
+                    push_window(&mut self.latencies, latency_ms);
+                    latency_ms
+                })
+            }
+            Event::Synchronize(sync_event) if sync_event.kind == SynchronizeKind::Dropped => {
+                self.dropped += 1;
+                None
+            }
+            _ => {
+                self.frame_size += 1;
+                None
+            }
+        }
+    }
+
+    pub fn record_parse_failure(&mut self) {
+        self.parse_failures += 1;
+    }
+
+    pub fn report(&mut self) -> StatsReport {
+        let now = Instant::now();
+        let (last_time, last_events) = self.last_report;
+        let elapsed = now.duration_since(last_time).as_secs_f64();
+        self.last_report = (now, self.events);
+
+        let latency_ms = if self.latencies.is_empty() {
+            None
+        } else {
+            let mut sorted: Vec<f64> = self.latencies.iter().copied().collect();
+            sorted.sort_by(|a, b| a.total_cmp(b));
+            Some((
+                sorted[0],
+                percentile(&sorted, 0.5),
+                percentile(&sorted, 0.99),
+            ))
+        };
+
+        StatsReport {
+            events: self.events,
+            event_rate: if elapsed > 0.0 {
+                (self.events - last_events) as f64 / elapsed
+            } else {
+                0.0
+            },
+            frames: self.frames,
+            average_frame_size: if self.frame_sizes.is_empty() {
+                0.0
+            } else {
+                self.frame_sizes.iter().sum::<usize>() as f64 / self.frame_sizes.len() as f64
+            },
+            max_frame_size: self.frame_sizes.iter().copied().max().unwrap_or(0),
+            latency_ms,
+            dropped: self.dropped,
+            parse_failures: self.parse_failures,
+        }
+    }
+}
+
+impl fmt::Display for StatsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} events ({:.1}/s), {} frames (avg {:.1}, max {} events), ",
+            self.events, self.event_rate, self.frames, self.average_frame_size, self.max_frame_size
+        )?;
+
+        match self.latency_ms {
+            None => write!(f, "latency unavailable, ")?,
+            Some((minimum, median, p99)) => write!(
+                f,
+                "latency min {:.1}ms median {:.1}ms p99 {:.1}ms, ",
+                minimum, median, p99
+            )?,
+        }
+
+        write!(
+            f,
+            "{} dropped, {} parse failures",
+            self.dropped, self.parse_failures
+        )
+    }
+}
+
+// Samples the device monotonic clock a few times and keeps the sample with the shortest round
+// trip. getevent timestamps events with CLOCK_MONOTONIC, /proc/uptime is not used as it counts
+// the time spent suspended and would be off by it after the tablet slept. The "now at" line of
+// /proc/timer_list is the monotonic clock in nanoseconds.
+pub fn estimate_clock_offset(source: &dyn EventSource, epoch: Instant) -> Option<f64> {
+    let mut best: Option<(Duration, f64)> = None;
+
+    for _ in 0..CLOCK_SAMPLES {
+        let mut output = vec![];
+
+        let before = Instant::now();
+        source
+            .shell(&["head", "-n", "3", "/proc/timer_list"], &mut output)
+            .ok()?;
+        let after = Instant::now();
+
+        let monotonic = parse_timer_list_now(&String::from_utf8_lossy(&output))?;
+
+        let round_trip = after.duration_since(before);
+        let midpoint = (before.duration_since(epoch) + round_trip / 2).as_secs_f64();
+        let offset = monotonic - midpoint;
+
+        if best.is_none_or(|(best_round_trip, _)| round_trip < best_round_trip) {
+            best = Some((round_trip, offset));
+        }
+    }
+
+    best.map(|(_, offset)| offset)
+}
+
+// now at 1338055981824313 nsecs
+fn parse_timer_list_now(input: &str) -> Option<f64> {
+    let nanoseconds: u64 = input
+        .lines()
+        .find_map(|x| x.strip_prefix("now at ")?.strip_suffix(" nsecs"))?
+        .parse()
+        .ok()?;
+    Some(nanoseconds as f64 / 1_000_000_000.0)
+}
+
+fn push_window<T>(window: &mut VecDeque<T>, value: T) {
+    if window.len() == WINDOW {
+        window.pop_front();
+    }
+    window.push_back(value);
+}
+
+fn percentile(sorted: &[f64], ratio: f64) -> f64 {
+    let index = ((sorted.len() - 1) as f64 * ratio).round() as usize;
+    sorted[index]
+}
+
+fn event_seconds(time: &EventTime) -> f64 {
+    time.seconds() as f64 + time.microseconds() as f64 / 1_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input_linux::{AbsoluteAxis, AbsoluteEvent, SynchronizeEvent};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn it_computes_latency_and_frame_sizes() {
+        let epoch = Instant::now();
+        // Device monotonic time 100s corresponds to the epoch.
+        let mut stats = Stats::new(epoch, Some(100.0));
+
+        for (index, latency_ms) in [5u64, 10, 20].iter().enumerate() {
+            let device_time = EventTime::new(100 + index as i64, 0);
+            let received_at =
+                epoch + Duration::from_secs(index as u64) + Duration::from_millis(*latency_ms);
+
+            let absolute = Event::Absolute(AbsoluteEvent::new(device_time, AbsoluteAxis::X, 1));
+            stats.record_event(&absolute, received_at);
+            stats.record_event(&absolute, received_at);
+            stats.record_event(
+                &Event::Synchronize(SynchronizeEvent::report(device_time)),
+                received_at,
+            );
+        }
+        stats.record_parse_failure();
+
+        let report = stats.report();
+        let (minimum, median, p99) = report.latency_ms.unwrap();
+
+        assert_eq!(report.events, 9);
+        assert_eq!(report.frames, 3);
+        assert_eq!(report.max_frame_size, 2);
+        assert_eq!(report.parse_failures, 1);
+        assert!((minimum - 5.0).abs() < 0.01);
+        assert!((median - 10.0).abs() < 0.01);
+        assert!((p99 - 20.0).abs() < 0.01);
+
+        assert_eq!(
+            parse_timer_list_now(
+                "Timer List Version: v0.9\nHRTIMER_MAX_CLOCK_BASES: 8\nnow at 1338055981824313 nsecs\n"
+            ),
+            Some(1338055.981824313)
+        );
+        assert_eq!(
+            parse_timer_list_now("cat: /proc/timer_list: Permission denied\n"),
+            None
+        );
+    }
+}
@@ -0,0 +1,305 @@
+use clap::ValueEnum;
+use input_linux::{
+    AbsoluteAxis, AbsoluteEvent, AbsoluteInfo, AbsoluteInfoSetup, Event, SynchronizeKind,
+};
+
+use crate::{error::Error, parser::ADBDevice};
+
+const TILT_MAXIMUM: i32 = 90;
+// Units per radian when one unit is one degree, as reported by Wacom tablets.
+const DEGREE_RESOLUTION: i32 = 57;
+const ROTATION_MINIMUM_TILT: f64 = 1.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum RotationAxis {
+    // -900..899 over a full turn, like the Wacom Art Pen
+    Z,
+    // 0..359 degrees
+    Wheel,
+}
+
+impl RotationAxis {
+    fn axis(self) -> AbsoluteAxis {
+        match self {
+            RotationAxis::Z => AbsoluteAxis::Z,
+            RotationAxis::Wheel => AbsoluteAxis::Wheel,
+        }
+    }
+
+    fn range(self) -> (i32, i32) {
+        match self {
+            RotationAxis::Z => (-900, 899),
+            RotationAxis::Wheel => (0, 359),
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct TiltArgs {
+    #[arg(long)]
+    tilt_degrees: bool,
+
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        default_value_t = 0.0,
+        allow_negative_numbers = true
+    )]
+    tilt_offset_x: f64,
+
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        default_value_t = 0.0,
+        allow_negative_numbers = true
+    )]
+    tilt_offset_y: f64,
+
+    #[arg(long, default_value_t = 1.0)]
+    tilt_scale: f64,
+
+    #[arg(long, value_enum)]
+    tilt_rotation: Option<RotationAxis>,
+}
+
+impl TiltArgs {
+    // Any calibration needs tilt in known units, so it implies the conversion to degrees.
+    pub fn converter(
+        &self,
+        device: &ADBDevice,
+        rotated: bool,
+    ) -> Result<Option<TiltConverter>, Error> {
+        if !self.tilt_degrees
+            && self.tilt_offset_x == 0.0
+            && self.tilt_offset_y == 0.0
+            && self.tilt_scale == 1.0
+            && self.tilt_rotation.is_none()
+        {
+            return Ok(None);
+        }
+
+        let units_per_degree = |axis| {
+            device
+                .events
+                .absolute
+                .iter()
+                .find(|x| x.axis == axis)
+                .map(|x| units_per_degree(&x.info))
+        };
+        let (Some(x), Some(y)) = (
+            units_per_degree(AbsoluteAxis::TiltX),
+            units_per_degree(AbsoluteAxis::TiltY),
+        ) else {
+            return Err(Error::InvalidArguments(format!(
+                "{} has no tilt axes, --tilt-* options cannot be applied",
+                device.name
+            )));
+        };
+
+        Ok(Some(TiltConverter {
+            units_per_degree: if rotated { (y, x) } else { (x, y) },
+            offset: (self.tilt_offset_x, self.tilt_offset_y),
+            scale: self.tilt_scale,
+            rotation: self.tilt_rotation,
+            tilt: (0.0, 0.0),
+            changed: false,
+            last_rotation: None,
+        }))
+    }
+}
+
+// Without a resolution, small ranges are taken as degrees and larger ones as spanning +-90.
+fn units_per_degree(info: &AbsoluteInfo) -> f64 {
+    if info.resolution > 0 {
+        info.resolution as f64 * std::f64::consts::PI / 180.0
+    } else if info.maximum <= TILT_MAXIMUM {
+        1.0
+    } else {
+        info.maximum as f64 / TILT_MAXIMUM as f64
+    }
+}
+
+pub struct TiltConverter {
+    units_per_degree: (f64, f64),
+    offset: (f64, f64),
+    scale: f64,
+    rotation: Option<RotationAxis>,
+    tilt: (f64, f64),
+    changed: bool,
+    last_rotation: Option<i32>,
+}
+
+impl TiltConverter {
+    pub fn configure(&self, device: &mut ADBDevice) {
+        let info = |minimum, maximum, resolution| AbsoluteInfo {
+            value: 0,
+            minimum,
+            maximum,
+            fuzz: 0,
+            flat: 0,
+            resolution,
+        };
+
+        for abs_setup in device.events.absolute.iter_mut() {
+            if matches!(abs_setup.axis, AbsoluteAxis::TiltX | AbsoluteAxis::TiltY) {
+                abs_setup.info = info(-TILT_MAXIMUM, TILT_MAXIMUM, DEGREE_RESOLUTION);
+            }
+        }
+
+        if let Some(rotation) = self.rotation {
+            let (minimum, maximum) = rotation.range();
+            device.events.absolute.retain(|x| x.axis != rotation.axis());
+            device.events.absolute.push(AbsoluteInfoSetup {
+                axis: rotation.axis(),
+                info: info(minimum, maximum, 0),
+            });
+        }
+    }
+
+    pub fn process(&mut self, event: Event) -> Vec<Event> {
+        match event {
+            Event::Absolute(mut absolute_event) => {
+                let (units_per_degree, offset) = match absolute_event.axis {
+                    AbsoluteAxis::TiltX => (self.units_per_degree.0, self.offset.0),
+                    AbsoluteAxis::TiltY => (self.units_per_degree.1, self.offset.1),
+                    _ => return vec![event],
+                };
+
+                let degrees = (absolute_event.value as f64 / units_per_degree * self.scale
+                    + offset)
+                    .clamp(-TILT_MAXIMUM as f64, TILT_MAXIMUM as f64);
+
+                if absolute_event.axis == AbsoluteAxis::TiltX {
+                    self.tilt.0 = degrees;
+                } else {
+                    self.tilt.1 = degrees;
+                }
+                self.changed = true;
+
+                absolute_event.value = degrees.round() as i32;
+                vec![Event::Absolute(absolute_event)]
+            }
+            Event::Synchronize(sync_event) if sync_event.kind == SynchronizeKind::Report => {
+                let mut output = vec![];
+
+                if let (Some(rotation), true) = (self.rotation, std::mem::take(&mut self.changed)) {
+                    // The pen azimuth, 0 pointing right and growing clockwise like screen angles.
+                    let (tilt_x, tilt_y) = self.tilt;
+                    if tilt_x.hypot(tilt_y) >= ROTATION_MINIMUM_TILT {
+                        let azimuth = tilt_y.atan2(tilt_x).to_degrees().rem_euclid(360.0);
+                        let (minimum, maximum) = rotation.range();
+                        let value = (minimum
+                            + (azimuth / 360.0 * (maximum - minimum + 1) as f64) as i32)
+                            .min(maximum);
+
+                        if self.last_rotation != Some(value) {
+                            self.last_rotation = Some(value);
+                            output.push(Event::Absolute(AbsoluteEvent::new(
+                                sync_event.time,
+                                rotation.axis(),
+                                value,
+                            )));
+                        }
+                    }
+                }
+
+                output.push(event);
+                output
+            }
+            _ => vec![event],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_devices;
+    use clap::Parser;
+    use input_linux::{EventTime, SynchronizeEvent};
+    use pretty_assertions::assert_eq;
+
+    #[derive(Parser)]
+    struct TestCli {
+        #[command(flatten)]
+        tilt: TiltArgs,
+    }
+
+    fn tilt(axis: AbsoluteAxis, value: i32) -> Event {
+        Event::Absolute(AbsoluteEvent::new(EventTime::default(), axis, value))
+    }
+
+    fn report() -> Event {
+        Event::Synchronize(SynchronizeEvent::report(EventTime::default()))
+    }
+
+    #[test]
+    fn it_converts_and_calibrates_tilt() {
+        let devices = parse_devices(include_str!("../devices.txt")).unwrap();
+        let mut pen = devices.into_iter().find(|x| x.name == "sec_e-pen").unwrap();
+
+        let args = TestCli::parse_from(["test"]).tilt;
+        assert!(args.converter(&pen, false).unwrap().is_none());
+
+        let args = TestCli::parse_from([
+            "test",
+            "--tilt-offset-x",
+            "-5",
+            "--tilt-scale",
+            "1.5",
+            "--tilt-rotation",
+            "z",
+        ])
+        .tilt;
+        let mut tiltless = pen.clone();
+        tiltless
+            .events
+            .absolute
+            .retain(|x| x.axis != AbsoluteAxis::TiltX && x.axis != AbsoluteAxis::TiltY);
+        assert!(args.converter(&tiltless, false).is_err());
+
+        let mut converter = args.converter(&pen, false).unwrap().unwrap();
+        converter.configure(&mut pen);
+
+        let rotation = pen
+            .events
+            .absolute
+            .iter()
+            .find(|x| x.axis == AbsoluteAxis::Z)
+            .unwrap();
+        assert_eq!((rotation.info.minimum, rotation.info.maximum), (-900, 899));
+        let tilt_x = pen
+            .events
+            .absolute
+            .iter()
+            .find(|x| x.axis == AbsoluteAxis::TiltX)
+            .unwrap();
+        assert_eq!(
+            (
+                tilt_x.info.minimum,
+                tilt_x.info.maximum,
+                tilt_x.info.resolution
+            ),
+            (-90, 90, 57)
+        );
+
+        let output: Vec<Event> = [
+            tilt(AbsoluteAxis::TiltX, 10),
+            tilt(AbsoluteAxis::TiltY, 70),
+            report(),
+        ]
+        .into_iter()
+        .flat_map(|event| converter.process(event))
+        .collect();
+
+        assert_eq!(
+            output,
+            vec![
+                tilt(AbsoluteAxis::TiltX, 10),
+                tilt(AbsoluteAxis::TiltY, 90),
+                tilt(AbsoluteAxis::Z, -482),
+                report(),
+            ]
+        );
+    }
+}